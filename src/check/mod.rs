@@ -0,0 +1,245 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::ask;
+use crate::paste::parse;
+use crate::paste::parse::Diagnostic;
+use crate::paste::parse::LinePart;
+use crate::paste::parse::Severity;
+use crate::paste::parse::Token;
+
+pub fn main(args: impl IntoIterator<Item = String>) -> Result<(), Box<dyn Error>> {
+    let mut args = args
+        .into_iter()
+        .peekable();
+
+    let mut strict = false;
+    while let Some(arg) = args.next_if(|arg| arg.starts_with('-')) {
+        match &*arg {
+            "--strict" => strict = true,
+            _ => return Err(format!("Unknown option: {arg}").into()),
+        }
+    }
+
+    let mut targets = args.collect::<Vec<_>>();
+    if targets.is_empty() {
+        targets.push(
+            match &*ask("Snippet files or directories to check (Default: `.`)")? {
+                "" => ".".to_string(),
+                target => target.to_string(),
+            },
+        );
+    }
+
+    let mut files = Vec::new();
+    for target in &targets {
+        collect_files(Path::new(target), &mut files)?;
+    }
+
+    let mut errors = 0;
+    let mut warnings = 0;
+    for file in &files {
+        let diagnostics = match read(file) {
+            Ok(source) => check(&source)?,
+            Err(e) => {
+                errors += 1;
+                println!("{}: error: {e}", file.display());
+                continue;
+            },
+        };
+
+        for Diagnostic {
+            severity,
+            message,
+            token,
+        } in diagnostics
+        {
+            match severity {
+                Severity::Warning => warnings += 1,
+                Severity::Error => errors += 1,
+            }
+            println!(
+                "{}:{}:{}: {severity}: {message}\n{token}\n",
+                file.display(),
+                token.line,
+                token.column
+            );
+        }
+    }
+
+    println!(
+        "Checked {} file(s): {errors} error(s), {warnings} warning(s)",
+        files.len()
+    );
+    if errors > 0 || strict && warnings > 0 {
+        return Err("Some snippets did not pass the check".into());
+    }
+    Ok(())
+}
+
+/// Collect the path, or the files in it if it is a directory (skipping hidden entries).
+pub(crate) fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    for entry in entries {
+        let hidden = entry
+            .file_name()
+            .is_some_and(|name| {
+                name.to_string_lossy()
+                    .starts_with('.')
+            });
+        if !hidden {
+            collect_files(&entry, files)?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn read(path: &Path) -> io::Result<String> {
+    let mut source = String::new();
+    File::open(path)?.read_to_string(&mut source)?;
+    Ok(source)
+}
+
+/// Every diagnostic of the snippet, ordered by position.
+fn check(source: &str) -> io::Result<Vec<Diagnostic>> {
+    let (snippet, errors) = parse::snippet_with_errors(Box::new(io::Cursor::new(
+        source.to_string(),
+    )))?;
+
+    let mut diagnostics = snippet.diagnostics;
+    // the declared arguments can be used in the included snippets, which are not loaded here
+    if snippet
+        .includes
+        .is_empty()
+    {
+        diagnostics.extend(parse::unused_declarations(
+            &snippet.lines,
+            &snippet.declared,
+        ));
+    }
+    diagnostics.extend(
+        errors
+            .iter()
+            .filter_map(parse::Error::diagnostic),
+    );
+    diagnostics.extend(unused_args(
+        &snippet.lines,
+        &snippet.line_numbers,
+        source,
+    ));
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.token.line, diagnostic.token.column));
+    Ok(diagnostics)
+}
+
+fn arg_names(line: &[LinePart]) -> impl Iterator<Item = &str> {
+    line.iter()
+        .filter_map(|part| match part {
+            LinePart::Arg(name, _) | LinePart::ArgList(name, _) => Some(name.as_str()),
+            LinePart::If(condition) => Some(condition.arg.as_str()),
+            _ => None,
+        })
+}
+
+/// Arguments written only in processed comments are asked, but never typed (or checked).
+fn unused_args(lines: &[Vec<LinePart>], line_numbers: &[usize], source: &str) -> Vec<Diagnostic> {
+    let typed = lines
+        .iter()
+        .filter(|line| !parse::is_comment(line))
+        .flat_map(|line| arg_names(line))
+        .collect::<HashSet<_>>();
+    let sources = source
+        .lines()
+        .collect::<Vec<_>>();
+
+    let mut reported = HashSet::new();
+    let mut diagnostics = Vec::new();
+    for (line, &number) in lines
+        .iter()
+        .zip(line_numbers)
+    {
+        if !parse::is_comment(line) {
+            continue;
+        }
+
+        for name in arg_names(line) {
+            if typed.contains(name) || !reported.insert(name) {
+                continue;
+            }
+
+            // point at the argument, or at the whole line if it was joined from the next ones
+            let text = sources
+                .get(number - 1)
+                .copied()
+                .unwrap_or_default();
+            let (column, token) = ["$@", "$["]
+                .iter()
+                .find_map(|start| {
+                    let token = format!("{start}{name}");
+                    text.find(&token)
+                        .map(|at| (text[..at].chars().count() + 1, token))
+                })
+                .unwrap_or((1, text.to_string()));
+
+            diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                message: format!("Argument `{name}` is only used in comments, it is never typed"),
+                token: Token {
+                    text: token,
+                    line: number,
+                    column,
+                    source: text.to_string(),
+                },
+            });
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let text = "\
+            ## $@host$ $@user::root$ $@unused$\n\
+            ssh $@user::admin$@$@host$\n\
+            $!Control+Shfit$\n\
+            sleep $'1s$\n\
+            echo $@incomplete\n\
+            ## $@unused$\n\
+            exit \\";
+
+        let found = check(text)
+            .unwrap()
+            .into_iter()
+            .map(|d| (d.severity, d.token.line, d.token.column))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                (Severity::Warning, 1, 26),
+                (Severity::Warning, 2, 5),
+                (Severity::Error, 3, 11),
+                (Severity::Error, 4, 7),
+                (Severity::Warning, 5, 6),
+                (Severity::Warning, 7, 6),
+            ]
+        );
+    }
+}
@@ -0,0 +1,34 @@
+use std::error::Error;
+
+#[cfg(target_os = "windows")]
+pub fn get_text() -> Result<String, Box<dyn Error>> {
+    use clipboard_win::formats;
+    use clipboard_win::get_clipboard;
+
+    get_clipboard(formats::Unicode).map_err(|e| format!("Failed to read clipboard: {e}").into())
+}
+
+#[cfg(target_os = "windows")]
+pub fn set_text(text: &str) -> Result<(), Box<dyn Error>> {
+    use clipboard_win::formats;
+    use clipboard_win::set_clipboard;
+
+    set_clipboard(formats::Unicode, text)
+        .map_err(|e| format!("Failed to write clipboard: {e}").into())
+}
+
+// TODO other platforms
+#[cfg(not(target_os = "windows"))]
+pub fn get_text() -> Result<String, Box<dyn Error>> { Err(unsupported()) }
+
+#[cfg(not(target_os = "windows"))]
+pub fn set_text(_text: &str) -> Result<(), Box<dyn Error>> { Err(unsupported()) }
+
+#[cfg(not(target_os = "windows"))]
+fn unsupported() -> Box<dyn Error> {
+    format!(
+        "Cannot access the clipboard on your platform ({})",
+        std::env::consts::OS
+    )
+    .into()
+}
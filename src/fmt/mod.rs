@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::stdin;
+use std::io::stdout;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+
+use crate::ask;
+use crate::check::collect_files;
+use crate::check::read;
+use crate::paste::parse;
+use crate::paste::syntax::Node;
+use crate::paste::syntax::NodeKind;
+use crate::paste::syntax::Tree;
+
+/// Indentation of lines joined to the previous one with `\`.
+const CONTINUATION_INDENT: &str = "    ";
+
+pub fn main(args: impl IntoIterator<Item = String>) -> Result<(), Box<dyn Error>> {
+    let mut args = args
+        .into_iter()
+        .peekable();
+
+    let mut check = false;
+    while let Some(arg) = args.next_if(|arg| arg.starts_with('-') && arg != "-") {
+        match &*arg {
+            "--check" => check = true,
+            _ => return Err(format!("Unknown option: {arg}").into()),
+        }
+    }
+
+    let mut targets = args.collect::<Vec<_>>();
+    if targets.is_empty() {
+        targets.push(
+            match &*ask("Snippet files or directories to format (Default: `-` for stdin)")? {
+                "" => "-".to_string(),
+                target => target.to_string(),
+            },
+        );
+    }
+
+    let mut unformatted = 0;
+    for target in &targets {
+        if target == "-" {
+            let mut source = String::new();
+            stdin().read_to_string(&mut source)?;
+            let formatted = format(&source);
+            match check {
+                true => unformatted += usize::from(formatted != source),
+                false => stdout().write_all(formatted.as_bytes())?,
+            }
+            continue;
+        }
+
+        let mut files = Vec::new();
+        collect_files(Path::new(target), &mut files)?;
+        for file in files {
+            let source = read(&file)?;
+            let formatted = format(&source);
+            if formatted == source {
+                continue;
+            }
+
+            match check {
+                true => {
+                    unformatted += 1;
+                    println!("{} is not formatted", file.display());
+                },
+                false => {
+                    fs::write(&file, formatted)?;
+                    println!("Formatted {}", file.display());
+                },
+            }
+        }
+    }
+
+    if unformatted > 0 {
+        return Err(format!("{unformatted} snippet(s) are not formatted").into());
+    }
+    Ok(())
+}
+
+/// Whether the token can be written differently, without changing how the snippet is parsed.
+fn rewritable(tree: &Tree, node: &Node) -> bool {
+    node.complete
+        && !tree
+            .text(node)
+            .contains('\n')
+}
+
+/// Normalize key combo spelling, put every argument default at the first use of the argument and
+/// indent continued lines.
+pub fn format(source: &str) -> String {
+    let tree = Tree::parse(source);
+
+    // the default used is the last one given (earlier ones are ignored when parsing),
+    // it is only moved if every use of the argument can be rewritten
+    let mut defaults = HashMap::<&str, Option<&str>>::new();
+    let mut movable = HashMap::<&str, bool>::new();
+    for node in &tree.nodes {
+        if let NodeKind::Arg {
+            name,
+            kind,
+            default,
+            ..
+        } = &node.kind
+        {
+            if let (Some(default), true) = (default, node.complete) {
+                defaults.insert(name, Some(default));
+            }
+            // a `:` right before an added `::` would start the default earlier
+            let written = kind
+                .as_ref()
+                .map_or(name.as_str(), |kind| kind);
+            *movable
+                .entry(name)
+                .or_insert(true) &= rewritable(&tree, node)
+                && !written.ends_with(':')
+                && kind.as_deref() != Some("");
+        }
+    }
+
+    let mut result = String::new();
+    for (i, node) in tree
+        .nodes
+        .iter()
+        .enumerate()
+    {
+        let text = tree.text(node);
+        match &node.kind {
+            NodeKind::Continuation => {
+                let line_break = text.trim_end_matches(|ch: char| ch.is_whitespace() && ch != '\n');
+                result.push_str(line_break);
+                // no indentation for empty lines
+                if line_break.ends_with('\n')
+                    && tree
+                        .nodes
+                        .get(i + 1)
+                        .is_some_and(|next| next.kind != NodeKind::Newline)
+                {
+                    result.push_str(CONTINUATION_INDENT);
+                }
+            },
+
+            _ if !rewritable(&tree, node) => result.push_str(text),
+
+            NodeKind::Arg {
+                name,
+                filters,
+                kind,
+                list,
+                ..
+            } if movable[name.as_str()] => {
+                result.push_str(match list {
+                    true => "$[",
+                    false => "$@",
+                });
+                result.push_str(name);
+                for filter in filters {
+                    result.push('|');
+                    result.push_str(filter);
+                }
+                if let Some(kind) = kind {
+                    result.push(':');
+                    result.push_str(kind);
+                }
+                // only the first use gets the default
+                if let Some(Some(default)) = defaults.insert(name, None) {
+                    result.push_str("::");
+                    result.push_str(default);
+                }
+                result.push('$');
+            },
+
+            NodeKind::KeyCombo(keys) => {
+                result.push_str("$!");
+                result.push_str(
+                    &keys
+                        .iter()
+                        .filter(|key| !key.is_empty())
+                        .map(|key| parse::key_name(key))
+                        .collect::<Vec<_>>()
+                        .join("+"),
+                );
+                result.push('$');
+            },
+
+            _ => result.push_str(text),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let source = "\
+            ## $@host$ $@user::root$\n\
+            ssh $@user|shquote::admin$@$@host:hostname::example.com$ \\\n\
+            \t$!ctrl + c$ $!Alt+$ \\\n\
+            \x20 $@host$ $!Shfit$ $@incomplete::x\n\
+            $@split\\\n  name::a$ $@split\\\n  name::b$\n";
+
+        let formatted = format(source);
+        assert_eq!(
+            formatted,
+            "\
+            ## $@host::example.com$ $@user::admin$\n\
+            ssh $@user|shquote$@$@host:hostname$ \\\n\
+            \x20   $!Control+c$ $!Alt$ \\\n\
+            \x20   $@host$ $!Shfit$ $@incomplete::x\n\
+            $@split\\\n  name::a$ $@split\\\n  name::b$\n"
+        );
+        assert_eq!(format(&formatted), formatted);
+
+        let parsed = |source: &str| {
+            let (snippet, _) =
+                parse::snippet_with_errors(Box::new(Cursor::new(source.to_string())))
+                    .unwrap();
+            (snippet.lines, snippet.defaults)
+        };
+        let (lines, defaults) = parsed(source);
+        let (formatted_lines, formatted_defaults) = parsed(&formatted);
+        assert_eq!(lines, formatted_lines);
+        assert_eq!(
+            defaults
+                .into_iter()
+                .collect::<HashMap<_, _>>(),
+            formatted_defaults
+                .into_iter()
+                .collect::<HashMap<_, _>>()
+        );
+    }
+}
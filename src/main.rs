@@ -1,8 +1,13 @@
+pub mod check;
+pub mod clipboard;
+pub mod fmt;
 pub mod paste;
 pub mod scan;
+pub mod secret;
 
 use std::env::args;
 use std::error::Error;
+use std::fs::File;
 use std::io;
 use std::io::stdin;
 use std::io::stdout;
@@ -33,6 +38,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     )?;
 
     ctrlc::set_handler(|| {
+        secret::restore_echo();
         eprintln!();
         error!("Cancelled");
 
@@ -49,6 +55,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     if let Err(e) = main_main() {
         error!("{e}");
+        exit(1);
     }
     Ok(())
 }
@@ -59,11 +66,13 @@ fn main_main() -> Result<(), Box<dyn Error>> {
     loop {
         let subcommand = match first_arg.clone() {
             Some(x) => x,
-            None => ask("Enter subcommand (`paste`/`p`, `scan`/`s`)")?,
+            None => ask("Enter subcommand (`paste`/`p`, `scan`/`s`, `check`/`c`, `fmt`/`f`)")?,
         };
         return match &*subcommand {
             "paste" | "p" | "v" => paste::main(args),
             "scan" | "s" => scan::main(args),
+            "check" | "c" => check::main(args),
+            "fmt" | "f" => fmt::main(args),
             _ => {
                 warn!("Unknown subcommand: {subcommand}");
                 first_arg = None;
@@ -74,12 +83,36 @@ fn main_main() -> Result<(), Box<dyn Error>> {
 }
 
 pub(crate) fn ask(question: &str) -> io::Result<String> {
+    Ok(ask_line(question)?.unwrap_or_default())
+}
+
+/// Like `ask`, but `None` when there is no more input to read.
+pub(crate) fn ask_line(question: &str) -> io::Result<Option<String>> {
     print!("{}: ", question);
     stdout().flush()?;
 
     let mut input = String::new();
-    stdin().read_line(&mut input)?;
-    Ok(input
-        .trim()
-        .to_string())
+    if stdin().read_line(&mut input)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(
+        input
+            .trim()
+            .to_string(),
+    ))
+}
+
+pub(crate) fn create_file(path: &str) -> io::Result<Option<File>> {
+    match File::create_new(path) {
+        Ok(file) => Ok(Some(file)),
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+            let answer = ask("File already exists. Overwrite? (`y` / anything else)")?
+                .to_lowercase();
+            if answer != "y" && answer != "yes" {
+                return Ok(None);
+            }
+            File::create(path).map(Some)
+        },
+        Err(err) => Err(err),
+    }
 }
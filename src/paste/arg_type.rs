@@ -0,0 +1,190 @@
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+
+/// What values an argument accepts, declared like `$@Port:port$` or `$@Distro:debian|arch$`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgType {
+    Integer,
+    Ipv4,
+    Ipv6,
+    /// IPv4 or IPv6.
+    Ip,
+    Hostname,
+    Port,
+    Path,
+    Boolean,
+    /// Any value, asked without echo and masked everywhere except the typed keys.
+    Secret,
+    /// One of the values, written as `a|b|c`.
+    Choice(Vec<String>),
+}
+
+impl ArgType {
+    pub const NAMES: &'static [&'static str] = &[
+        "int", "ipv4", "ipv6", "ip", "hostname", "port", "path", "bool", "secret",
+    ];
+
+    /// Parse a type name, or choices separated by `|`.
+    pub fn parse(text: &str) -> Option<Self> {
+        use ArgType::*;
+        Some(match text {
+            "int" | "integer" => Integer,
+            "ipv4" => Ipv4,
+            "ipv6" => Ipv6,
+            "ip" => Ip,
+            "hostname" | "host" => Hostname,
+            "port" => Port,
+            "path" => Path,
+            "bool" | "boolean" => Boolean,
+            "secret" => Secret,
+            _ if text.contains('|') => Choice(
+                text.split('|')
+                    .map(str::to_string)
+                    .collect(),
+            ),
+            _ => return None,
+        })
+    }
+
+    /// Check the value, explaining what is expected if it is not valid.
+    pub fn validate(&self, value: &str) -> Result<(), String> {
+        use ArgType::*;
+        let valid = match self {
+            Integer => value
+                .parse::<i64>()
+                .is_ok(),
+            Ipv4 => value
+                .parse::<Ipv4Addr>()
+                .is_ok(),
+            Ipv6 => value
+                .parse::<Ipv6Addr>()
+                .is_ok(),
+            Ip => value
+                .parse::<IpAddr>()
+                .is_ok(),
+            Hostname => is_hostname(value),
+            Port => value
+                .parse::<u16>()
+                .is_ok_and(|port| port != 0),
+            Path => !value.is_empty() && !value.contains(char::is_control),
+            Boolean => BOOLEANS.contains(&&*value.to_lowercase()),
+            Secret => true,
+            Choice(choices) => choices.iter().any(|choice| choice == value),
+        };
+
+        match valid {
+            true => Ok(()),
+            false => Err(format!("`{value}` is not {}", self.expected())),
+        }
+    }
+
+    fn expected(&self) -> String {
+        use ArgType::*;
+        match self {
+            Integer => "a whole number".to_string(),
+            Ipv4 => "an IPv4 address".to_string(),
+            Ipv6 => "an IPv6 address".to_string(),
+            Ip => "an IP address".to_string(),
+            Hostname => "a hostname (letters, digits, `-` and `.`)".to_string(),
+            Port => "a port number (1-65535)".to_string(),
+            Path => "a path".to_string(),
+            Boolean => format!("a boolean ({})", BOOLEANS.join("/")),
+            Secret => "a secret".to_string(),
+            Choice(choices) => format!(
+                "one of {}",
+                choices
+                    .iter()
+                    .map(|choice| format!("`{choice}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+impl Display for ArgType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use ArgType::*;
+        match self {
+            Integer => write!(f, "int"),
+            Ipv4 => write!(f, "ipv4"),
+            Ipv6 => write!(f, "ipv6"),
+            Ip => write!(f, "ip"),
+            Hostname => write!(f, "hostname"),
+            Port => write!(f, "port"),
+            Path => write!(f, "path"),
+            Boolean => write!(f, "bool"),
+            Secret => write!(f, "secret"),
+            Choice(choices) => write!(f, "{}", choices.join("|")),
+        }
+    }
+}
+
+const BOOLEANS: &[&str] = &["true", "false", "yes", "no", "y", "n", "1", "0"];
+
+/// Labels of letters, digits and inner `-`, joined with `.` (RFC 1123).
+fn is_hostname(value: &str) -> bool {
+    let value = value
+        .strip_suffix('.')
+        .unwrap_or(value);
+
+    !value.is_empty()
+        && value.len() <= 253
+        && value
+            .split('.')
+            .all(|label| {
+                (1..=63).contains(&label.len())
+                    && !label.starts_with('-')
+                    && !label.ends_with('-')
+                    && label
+                        .chars()
+                        .all(|ch| ch.is_ascii_alphanumeric() || ch == '-')
+            })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let valid = |kind: &str, value: &str| {
+            ArgType::parse(kind)
+                .unwrap()
+                .validate(value)
+                .is_ok()
+        };
+
+        assert!(valid("int", "-12"));
+        assert!(!valid("int", "12a"));
+        assert!(valid("ipv4", "10.0.0.1"));
+        assert!(!valid("ipv4", "10.0.0.256"));
+        assert!(valid("ipv6", "fe80::1"));
+        assert!(valid("ip", "fe80::1"));
+        assert!(!valid("ip", "localhost"));
+        assert!(valid("hostname", "db-1.example.com"));
+        assert!(!valid("hostname", "-db.example.com"));
+        assert!(!valid("hostname", "db_1"));
+        assert!(valid("port", "22"));
+        assert!(!valid("port", "0"));
+        assert!(!valid("port", "65536"));
+        assert!(valid("path", "/etc/nginx/nginx.conf"));
+        assert!(!valid("path", ""));
+        assert!(valid("bool", "Yes"));
+        assert!(!valid("bool", "maybe"));
+        assert!(valid("debian|arch|alpine", "arch"));
+        assert!(!valid("debian|arch|alpine", "Arch"));
+        assert!(ArgType::parse("number").is_none());
+
+        assert_eq!(
+            ArgType::parse("debian|arch")
+                .unwrap()
+                .validate("rhel"),
+            Err("`rhel` is not one of `debian`, `arch`".to_string())
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::io::Write;
+use std::thread::sleep;
+use std::time::Duration;
+
+use enigo::Direction;
+use enigo::Enigo;
+use enigo::Key;
+use enigo::Keyboard;
+
+use super::layout::PhysicalKey;
+
+/// Everything `paste` does to the keyboard goes through a backend.
+pub trait Backend {
+    fn key(&mut self, key: Key, direction: Direction) -> Result<(), Box<dyn Error>>;
+
+    /// Press the key at the physical position, no matter what the keyboard layout says it types.
+    fn physical(&mut self, key: PhysicalKey, direction: Direction) -> Result<(), Box<dyn Error>>;
+
+    /// Type the text as-is, no matter the keyboard layout.
+    fn text(&mut self, text: &str) -> Result<(), Box<dyn Error>>;
+
+    fn delay(&mut self, duration: Duration) -> Result<(), Box<dyn Error>> {
+        sleep(duration);
+        Ok(())
+    }
+}
+
+impl Backend for Enigo {
+    fn key(&mut self, key: Key, direction: Direction) -> Result<(), Box<dyn Error>> {
+        Keyboard::key(self, key, direction)?;
+        Ok(())
+    }
+
+    fn physical(&mut self, key: PhysicalKey, direction: Direction) -> Result<(), Box<dyn Error>> {
+        Keyboard::raw(self, key.raw(), direction)?;
+        Ok(())
+    }
+
+    fn text(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+        for ch in text.chars() {
+            Keyboard::key(
+                self,
+                Key::Unicode(ch),
+                Direction::Click,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Key(Key, Direction),
+    Physical(PhysicalKey, Direction),
+    Text(String),
+    Delay(Duration),
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use Event::*;
+        match self {
+            Key(key, Direction::Press) => write!(f, "press {key:?}"),
+            Key(key, Direction::Release) => write!(f, "release {key:?}"),
+            Key(key, Direction::Click) => write!(f, "click {key:?}"),
+            Physical(key, Direction::Press) => write!(f, "press physical {key}"),
+            Physical(key, Direction::Release) => write!(f, "release physical {key}"),
+            Physical(key, Direction::Click) => write!(f, "click physical {key}"),
+            Text(text) => write!(f, "text {text:?}"),
+            Delay(duration) => write!(f, "delay {}ms", duration.as_millis()),
+        }
+    }
+}
+
+/// Records the events in memory instead of sending them (delays are not waited for).
+impl Backend for Vec<Event> {
+    fn key(&mut self, key: Key, direction: Direction) -> Result<(), Box<dyn Error>> {
+        self.push(Event::Key(key, direction));
+        Ok(())
+    }
+
+    fn physical(&mut self, key: PhysicalKey, direction: Direction) -> Result<(), Box<dyn Error>> {
+        self.push(Event::Physical(key, direction));
+        Ok(())
+    }
+
+    fn text(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+        self.push(Event::Text(text.to_string()));
+        Ok(())
+    }
+
+    fn delay(&mut self, duration: Duration) -> Result<(), Box<dyn Error>> {
+        self.push(Event::Delay(duration));
+        Ok(())
+    }
+}
+
+/// Writes the events line by line instead of sending them (delays are not waited for).
+pub struct Recorder<W: Write>(pub W);
+
+impl<W: Write> Recorder<W> {
+    fn record(&mut self, event: Event) -> Result<(), Box<dyn Error>> {
+        writeln!(self.0, "{event}")?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Backend for Recorder<W> {
+    fn key(&mut self, key: Key, direction: Direction) -> Result<(), Box<dyn Error>> {
+        self.record(Event::Key(key, direction))
+    }
+
+    fn physical(&mut self, key: PhysicalKey, direction: Direction) -> Result<(), Box<dyn Error>> {
+        self.record(Event::Physical(key, direction))
+    }
+
+    fn text(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+        self.record(Event::Text(text.to_string()))
+    }
+
+    fn delay(&mut self, duration: Duration) -> Result<(), Box<dyn Error>> {
+        self.record(Event::Delay(duration))
+    }
+}
+
+/// Prints a human-readable timeline of the events instead of sending them.
+///
+/// Text typed character by character is joined into a single entry, together with the delays that
+/// follow each character.
+pub struct Preview<W: Write> {
+    writer: W,
+    text: String,
+    text_duration: Duration,
+    after_text: bool,
+    total: Duration,
+}
+
+impl<W: Write> Preview<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            text: String::new(),
+            text_duration: Duration::ZERO,
+            after_text: false,
+            total: Duration::ZERO,
+        }
+    }
+
+    fn flush_text(&mut self) -> Result<(), Box<dyn Error>> {
+        self.after_text = false;
+        if self.text.is_empty() {
+            return Ok(());
+        }
+
+        writeln!(
+            self.writer,
+            "type {:?} ({:?})",
+            self.text, self.text_duration
+        )?;
+        self.text.clear();
+        self.text_duration = Duration::ZERO;
+        Ok(())
+    }
+
+    /// Print the remaining text and the total duration of the timeline.
+    pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.flush_text()?;
+        writeln!(
+            self.writer,
+            "Total duration: {:?}",
+            self.total
+        )?;
+        self.writer
+            .flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Backend for Preview<W> {
+    fn key(&mut self, key: Key, direction: Direction) -> Result<(), Box<dyn Error>> {
+        self.flush_text()?;
+        writeln!(
+            self.writer,
+            "{}",
+            Event::Key(key, direction)
+        )?;
+        Ok(())
+    }
+
+    fn physical(&mut self, key: PhysicalKey, direction: Direction) -> Result<(), Box<dyn Error>> {
+        self.flush_text()?;
+        writeln!(
+            self.writer,
+            "{}",
+            Event::Physical(key, direction)
+        )?;
+        Ok(())
+    }
+
+    fn text(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+        self.text
+            .push_str(text);
+        self.after_text = true;
+        Ok(())
+    }
+
+    fn delay(&mut self, duration: Duration) -> Result<(), Box<dyn Error>> {
+        self.total += duration;
+
+        // delay right after a character is the typing speed
+        if self.after_text {
+            self.after_text = false;
+            self.text_duration += duration;
+            return Ok(());
+        }
+
+        self.flush_text()?;
+        writeln!(
+            self.writer,
+            "{}",
+            Event::Delay(duration)
+        )?;
+        Ok(())
+    }
+}
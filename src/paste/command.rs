@@ -0,0 +1,127 @@
+use std::error::Error;
+use std::process::Command;
+use std::process::Stdio;
+
+use indexmap::IndexMap;
+
+use super::parse::LinePart;
+use crate::secret;
+use crate::secret::Zeroing;
+
+/// The `$<` commands of the snippet, in order, possibly repeated.
+fn commands(lines: &[Vec<LinePart>]) -> impl Iterator<Item = &str> {
+    lines
+        .iter()
+        .flatten()
+        .filter_map(|part| match part {
+            LinePart::Command(command) => Some(command.as_str()),
+            _ => None,
+        })
+}
+
+/// The first `$<` command of the snippet, if it has any.
+pub fn first(lines: &[Vec<LinePart>]) -> Option<&str> {
+    commands(lines).next()
+}
+
+/// Run the command with the shell of the host, returning its output without the final line breaks.
+pub fn run(command: &str) -> Result<String, Box<dyn Error>> {
+    #[cfg(target_os = "windows")]
+    let mut shell = {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    };
+    #[cfg(not(target_os = "windows"))]
+    let mut shell = {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+
+    // the command can still ask for input, like a password prompt of `pass`
+    let output = shell
+        .arg(command)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("Could not run command `{command}`: {e}"))?;
+    if !output
+        .status
+        .success()
+    {
+        return Err(format!(
+            "Command `{command}` failed ({})",
+            output.status
+        )
+        .into());
+    }
+
+    // trimmed in place, so that no copy of the output is left around
+    let mut output = String::from_utf8(output.stdout)
+        .map_err(|_| format!("Command `{command}` did not output valid UTF-8"))?;
+    let length = output
+        .trim_end_matches(['\r', '\n'])
+        .len();
+    output.truncate(length);
+    Ok(output)
+}
+
+/// Run every distinct `$<` command of the lines once, giving their outputs by command.
+///
+/// The outputs are treated like secret arguments, as they are often tokens or keys.
+pub fn run_all(
+    lines: &[Vec<LinePart>],
+    run: impl Fn(&str) -> Result<String, Box<dyn Error>>,
+) -> Result<Zeroing<IndexMap<String, String>>, Box<dyn Error>> {
+    let mut outputs = Zeroing(IndexMap::new());
+    for command in commands(lines) {
+        if !outputs.contains_key(command) {
+            outputs.insert(command.to_string(), run(command)?);
+        }
+    }
+    Ok(outputs)
+}
+
+/// Masks shown instead of the outputs, without running the commands.
+pub fn masked(lines: &[Vec<LinePart>]) -> IndexMap<String, String> {
+    commands(lines)
+        .map(|command| (command.to_string(), secret::MASK.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::paste::parse;
+
+    #[test]
+    fn test_run_all() {
+        let parse::Snippet { lines, .. } = parse::snippet(Box::new(
+            "echo '$<cat key.pub$' >> keys\nid $<whoami$ $<cat key.pub$".as_bytes(),
+        ))
+        .unwrap();
+        assert_eq!(first(&lines), Some("cat key.pub"));
+
+        let runs = Cell::new(0);
+        let run = |command: &str| {
+            runs.set(runs.get() + 1);
+            Ok(match command {
+                "whoami" => "root".to_string(),
+                _ => "ssh-ed25519 AAAA".to_string(),
+            })
+        };
+
+        let outputs = run_all(&lines, run).unwrap();
+        assert_eq!(
+            *outputs,
+            IndexMap::from([
+                ("cat key.pub".to_string(), "ssh-ed25519 AAAA".to_string()),
+                ("whoami".to_string(), "root".to_string()),
+            ])
+        );
+        assert_eq!(runs.get(), 2);
+    }
+}
@@ -0,0 +1,170 @@
+use indexmap::IndexMap;
+
+use super::parse::Condition;
+use super::parse::LinePart;
+
+/// Whether the parts are typed, given the `$^...$` conditions started before them.
+#[derive(Debug, Clone, Default)]
+pub struct Conditions(Vec<bool>);
+
+impl Conditions {
+    pub fn active(&self) -> bool {
+        self.0
+            .last()
+            .copied()
+            .unwrap_or(true)
+    }
+
+    pub fn start(&mut self, condition: &Condition, arg_values: &IndexMap<String, String>) {
+        let active = self.active() && holds(condition, arg_values);
+        self.0
+            .push(active);
+    }
+
+    /// An end without a started condition is ignored.
+    pub fn end(&mut self) {
+        self.0
+            .pop();
+    }
+
+    /// Start or end a condition if the part is one.
+    pub fn update(&mut self, part: &LinePart, arg_values: &IndexMap<String, String>) {
+        match part {
+            LinePart::If(condition) => self.start(condition, arg_values),
+            LinePart::EndIf => self.end(),
+            _ => {},
+        }
+    }
+
+    /// Like `update`, but a condition on an argument that has no value yet does not hold.
+    pub fn update_asked(&mut self, part: &LinePart, arg_values: &IndexMap<String, String>) {
+        match part {
+            LinePart::If(condition) if !arg_values.contains_key(&condition.arg) => self
+                .0
+                .push(false),
+            part => self.update(part, arg_values),
+        }
+    }
+}
+
+fn holds(condition: &Condition, arg_values: &IndexMap<String, String>) -> bool {
+    let value = arg_values
+        .get(&condition.arg)
+        .map(String::as_str)
+        .unwrap_or_default();
+    match &condition.value {
+        Some(expected) => value == expected,
+        None => !value.is_empty(),
+    }
+}
+
+/// Drop the parts whose conditions do not hold, and the lines that are left with nothing to type.
+///
+/// Lines with no conditions on them are kept as they are (even if empty), unless they are inside
+/// a block of lines whose condition does not hold.
+pub fn resolve(
+    lines: Vec<Vec<LinePart>>,
+    arg_values: &IndexMap<String, String>,
+) -> Vec<Vec<LinePart>> {
+    let mut conditions = Conditions::default();
+    let mut result = Vec::new();
+
+    for line in lines {
+        let started_active = conditions.active();
+        let has_conditions = line
+            .iter()
+            .any(|part| matches!(part, LinePart::If(_) | LinePart::EndIf));
+
+        let mut kept = Vec::new();
+        for part in line {
+            conditions.update(&part, arg_values);
+            match part {
+                LinePart::If(_) | LinePart::EndIf => {},
+                // empty texts are left around the other parts
+                LinePart::Text(text) if text.is_empty() => {},
+                part if conditions.active() => kept.push(part),
+                _ => {},
+            }
+        }
+
+        if !kept.is_empty() || started_active && !has_conditions {
+            result.push(kept);
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paste::parse;
+
+    #[test]
+    fn test_resolve() {
+        let parse::Snippet { lines, .. } = parse::snippet(Box::new(
+            "\
+            apt install $^proxy$-o Acquire::http::Proxy=$@proxy$ $^$nginx\n\
+            $^distro=debian$\n\
+            apt update\n\
+            \n\
+            $^proxy$echo $@proxy$$^$\n\
+            $^$\n\
+            $^proxy$export http_proxy=$@proxy$$^$\n\
+            \n\
+            done"
+                .as_bytes(),
+        ))
+        .unwrap();
+
+        let arg_values = IndexMap::from([
+            ("proxy".to_string(), String::new()),
+            ("distro".to_string(), "arch".to_string()),
+        ]);
+
+        use LinePart::*;
+        assert_eq!(
+            resolve(lines.clone(), &arg_values),
+            [
+                &[
+                    Text("apt install ".to_string()),
+                    Text("nginx".to_string())
+                ][..],
+                &[],
+                &[Text("done".to_string())],
+            ]
+        );
+
+        let arg_values = IndexMap::from([
+            (
+                "proxy".to_string(),
+                "http://proxy".to_string(),
+            ),
+            ("distro".to_string(), "debian".to_string()),
+        ]);
+        assert_eq!(
+            resolve(lines, &arg_values),
+            [
+                &[
+                    Text("apt install ".to_string()),
+                    Text("-o Acquire::http::Proxy=".to_string()),
+                    Arg("proxy".to_string(), vec![]),
+                    Text(" ".to_string()),
+                    Text("nginx".to_string())
+                ][..],
+                &[Text("apt update".to_string())],
+                &[],
+                &[
+                    Text("echo ".to_string()),
+                    Arg("proxy".to_string(), vec![])
+                ],
+                &[
+                    Text("export http_proxy=".to_string()),
+                    Arg("proxy".to_string(), vec![])
+                ],
+                &[],
+                &[Text("done".to_string())],
+            ]
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use std::error::Error;
+
+use super::filter;
+use super::parse::EnvVar;
+use super::parse::LinePart;
+
+/// Replace the `$%` environment variables with their (filtered) values, or defaults if not set.
+///
+/// Variables are looked up with `lookup`, the ones without a value and a default are an error.
+pub fn resolve(
+    lines: Vec<Vec<LinePart>>,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<Vec<LinePart>>, Box<dyn Error>> {
+    let mut result = Vec::new();
+    for line in lines {
+        let mut resolved = Vec::new();
+        for part in line {
+            resolved.push(match part {
+                LinePart::Env(EnvVar {
+                    name,
+                    default,
+                    filters,
+                }) => {
+                    let value = lookup(&name)
+                        .or(default)
+                        .ok_or_else(|| {
+                            format!(
+                                "Environment variable `{name}` is not set, \
+                                and `$%{name}$` has no default (`$%{name}::default$`)"
+                            )
+                        })?;
+                    LinePart::Text(filter::apply(&filters, &value).to_string())
+                },
+                part => part,
+            });
+        }
+        result.push(resolved);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paste::parse;
+
+    #[test]
+    fn test_resolve() {
+        let parse::Snippet { lines, .. } = parse::snippet(Box::new(
+            "export http_proxy=$%PROXY_URL|shquote$ no_proxy=$%NO_PROXY::localhost$".as_bytes(),
+        ))
+        .unwrap();
+
+        let lookup = |name: &str| match name {
+            "PROXY_URL" => Some("http://proxy:3128/?a&b".to_string()),
+            _ => None,
+        };
+        use LinePart::*;
+        assert_eq!(
+            resolve(lines.clone(), lookup).unwrap(),
+            [[
+                Text("export http_proxy=".to_string()),
+                Text("'http://proxy:3128/?a&b'".to_string()),
+                Text(" no_proxy=".to_string()),
+                Text("localhost".to_string())
+            ]]
+        );
+
+        let error = resolve(lines, |_| None).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("`PROXY_URL` is not set"));
+    }
+}
@@ -0,0 +1,169 @@
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::secret::Zeroing;
+
+/// Transformation of an argument value before it is typed, written like `$@name|shquote$`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// Quote for POSIX shells, unless the value is safe as-is.
+    ShellQuote,
+    /// Quote for PowerShell, unless the value is safe as-is.
+    PowerShellQuote,
+    Upper,
+    Lower,
+    Trim,
+    Base64,
+    /// Percent-encode everything but unreserved characters (RFC 3986).
+    UrlEncode,
+    /// A JSON string literal, with the quotes.
+    Json,
+}
+
+impl Filter {
+    pub const NAMES: &'static [&'static str] = &[
+        "shquote", "psquote", "upper", "lower", "trim", "base64", "urlencode", "json",
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        use Filter::*;
+        Some(match name {
+            "shquote" => ShellQuote,
+            "psquote" => PowerShellQuote,
+            "upper" => Upper,
+            "lower" => Lower,
+            "trim" => Trim,
+            "base64" => Base64,
+            "urlencode" => UrlEncode,
+            "json" => Json,
+            _ => return None,
+        })
+    }
+
+    pub fn apply(&self, value: &str) -> String {
+        use Filter::*;
+        match self {
+            ShellQuote => quote(value, "'", "'\\''", |ch| "@%+=:,./-_".contains(ch)),
+            PowerShellQuote => quote(value, "'", "''", |ch| ".-_/\\:".contains(ch)),
+            Upper => value.to_uppercase(),
+            Lower => value.to_lowercase(),
+            Trim => value
+                .trim()
+                .to_string(),
+            Base64 => base64(value.as_bytes()),
+            UrlEncode => value
+                .bytes()
+                .map(|byte| match byte {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' =>
+                        char::from(byte).to_string(),
+                    _ => format!("%{byte:02X}"),
+                })
+                .collect(),
+            Json => {
+                let mut result = String::from('"');
+                for ch in value.chars() {
+                    match ch {
+                        '"' => result.push_str("\\\""),
+                        '\\' => result.push_str("\\\\"),
+                        '\n' => result.push_str("\\n"),
+                        '\r' => result.push_str("\\r"),
+                        '\t' => result.push_str("\\t"),
+                        ch if ch.is_control() => result.push_str(&format!("\\u{:04x}", ch as u32)),
+                        ch => result.push(ch),
+                    }
+                }
+                result.push('"');
+                result
+            },
+        }
+    }
+}
+
+impl Display for Filter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use Filter::*;
+        let name = match self {
+            ShellQuote => "shquote",
+            PowerShellQuote => "psquote",
+            Upper => "upper",
+            Lower => "lower",
+            Trim => "trim",
+            Base64 => "base64",
+            UrlEncode => "urlencode",
+            Json => "json",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Apply the filters in order, zeroing every intermediate value as the value might be secret.
+pub fn apply(filters: &[Filter], value: &str) -> Zeroing<String> {
+    let mut result = Zeroing(value.to_string());
+    for filter in filters {
+        result = Zeroing(filter.apply(&result));
+    }
+    result
+}
+
+/// Wrap the value in quotes, unless it is not empty and made of alphanumerics and `safe` ones.
+fn quote(value: &str, quote: &str, escaped: &str, safe: fn(char) -> bool) -> String {
+    if !value.is_empty()
+        && value
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || safe(ch))
+    {
+        return value.to_string();
+    }
+    format!("{quote}{}{quote}", value.replace(quote, escaped))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut result = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |group, (i, byte)| group | u32::from(*byte) << (16 - 8 * i));
+        // a chunk of n bytes gives n + 1 characters, the rest is padding
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => result.push(char::from(ALPHABET[(group >> (18 - 6 * i) & 0x3F) as usize])),
+                false => result.push('='),
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let filtered = |filters: &[&str], value: &str| {
+            let filters = filters
+                .iter()
+                .map(|name| Filter::parse(name).unwrap())
+                .collect::<Vec<_>>();
+            apply(&filters, value).to_string()
+        };
+
+        assert_eq!(filtered(&["shquote"], "/etc/hosts"), "/etc/hosts");
+        assert_eq!(filtered(&["shquote"], "it's here"), r"'it'\''s here'");
+        assert_eq!(filtered(&["shquote"], ""), "''");
+        assert_eq!(filtered(&["psquote"], "it's"), "'it''s'");
+        assert_eq!(filtered(&["trim", "upper"], " db "), "DB");
+        assert_eq!(filtered(&["lower"], "DB"), "db");
+        assert_eq!(filtered(&["base64"], "hello"), "aGVsbG8=");
+        assert_eq!(filtered(&["base64"], "hi!"), "aGkh");
+        assert_eq!(filtered(&["base64"], "a"), "YQ==");
+        assert_eq!(filtered(&["urlencode"], "a b&c/ä"), "a%20b%26c%2F%C3%A4");
+        assert_eq!(filtered(&["json"], "say \"hi\"\n"), r#""say \"hi\"\n""#);
+        assert_eq!(filtered(&[], "as is"), "as is");
+        assert!(Filter::parse("reverse").is_none());
+    }
+}
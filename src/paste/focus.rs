@@ -0,0 +1,110 @@
+use std::error::Error;
+use std::io::stderr;
+use std::io::Write;
+use std::time::Duration;
+
+use enigo::Direction::*;
+use enigo::Key;
+
+use super::backend::Backend;
+
+/// Environment variable used when `--focus` is not given.
+pub const FOCUS_VAR: &str = "SNIPPED_FOCUS";
+
+/// How the window to type into gets focused before typing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Focus {
+    /// Press `Alt+Tab` to return to the previous window.
+    #[default]
+    AltTab,
+    /// Wait for the given amount of seconds, so that the user can focus the window.
+    Countdown(u64),
+    /// Type into whatever window is currently focused.
+    Keep,
+}
+
+impl Focus {
+    /// Parse `alt-tab`, `countdown`, `countdown:<seconds>` or `none`.
+    pub fn parse(value: &str) -> Result<Self, Box<dyn Error>> {
+        use Focus::*;
+        Ok(match value.trim() {
+            "alt-tab" => AltTab,
+            "countdown" => Countdown(5),
+            "none" => Keep,
+            value => match value.split_once(':') {
+                Some(("countdown", seconds)) => Countdown(
+                    seconds
+                        .parse()
+                        .map_err(|_| format!("Expected seconds in focus `{value}`"))?,
+                ),
+                _ => return Err(format!("Unknown focus strategy `{value}`").into()),
+            },
+        })
+    }
+
+    pub fn switch(&self, backend: &mut dyn Backend) -> Result<(), Box<dyn Error>> {
+        use Focus::*;
+        match self {
+            AltTab => {
+                backend.key(Key::Alt, Press)?;
+                backend.key(Key::Tab, Press)?;
+                backend.key(Key::Tab, Release)?;
+                backend.key(Key::Alt, Release)?;
+            },
+            Countdown(seconds) => {
+                for left in (1..=*seconds).rev() {
+                    eprint!("\rTyping starts in {left}s, focus your window... ");
+                    stderr().flush()?;
+                    // through the backend, so that `--dry-run` counts it instead of waiting
+                    backend.delay(Duration::from_secs(1))?;
+                }
+                eprintln!();
+            },
+            Keep => {},
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paste::backend::Event;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Focus::parse("alt-tab").unwrap(), Focus::AltTab);
+        assert_eq!(Focus::parse("countdown").unwrap(), Focus::Countdown(5));
+        assert_eq!(Focus::parse(" countdown:3 ").unwrap(), Focus::Countdown(3));
+        assert_eq!(Focus::parse("none").unwrap(), Focus::Keep);
+        assert!(Focus::parse("countdown:soon").is_err());
+        assert!(Focus::parse("countdown:").is_err());
+        assert!(Focus::parse("click").is_err());
+        assert!(Focus::parse("").is_err());
+    }
+
+    #[test]
+    fn test_switch() {
+        let mut events = Vec::new();
+        Focus::AltTab
+            .switch(&mut events)
+            .unwrap();
+        Focus::Countdown(2)
+            .switch(&mut events)
+            .unwrap();
+        Focus::Keep
+            .switch(&mut events)
+            .unwrap();
+        assert_eq!(
+            events,
+            [
+                Event::Key(Key::Alt, Press),
+                Event::Key(Key::Tab, Press),
+                Event::Key(Key::Tab, Release),
+                Event::Key(Key::Alt, Release),
+                Event::Delay(Duration::from_secs(1)),
+                Event::Delay(Duration::from_secs(1)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,270 @@
+use indexmap::IndexMap;
+use serde::Deserialize;
+use toml::Spanned;
+use toml::Value;
+
+use super::arg_type::ArgType;
+use super::parse::ArgHelp;
+use crate::VERSION;
+
+/// Line before and after the front matter, which can only be at the start of the snippet.
+pub const DELIMITER: &str = "+++";
+
+/// What the snippet is, from its front matter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub author: Option<String>,
+    /// Oldest version of snipped that can paste the snippet.
+    pub snipped: Option<String>,
+}
+
+/// Argument declared in an `[args.Name]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Declaration {
+    pub default: Option<String>,
+    pub arg_type: Option<ArgType>,
+    pub help: Option<ArgHelp>,
+    /// Line of the table header.
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrontMatter {
+    pub metadata: Metadata,
+    /// Declared arguments, in the order they are asked.
+    pub args: IndexMap<String, Declaration>,
+}
+
+/// Message about a line of the front matter, with the line number.
+pub type Problem = (usize, String);
+
+/// The front matter as it is written, before it is checked.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Document {
+    name: Option<String>,
+    description: Option<String>,
+    author: Option<String>,
+    snipped: Option<Spanned<String>>,
+    #[serde(default)]
+    args: IndexMap<String, Spanned<Table>>,
+}
+
+/// An `[args.Name]` table as it is written.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Table {
+    default: Option<Spanned<Value>>,
+    #[serde(rename = "type")]
+    arg_type: Option<Spanned<String>>,
+    help: Option<String>,
+    example: Option<String>,
+}
+
+/// Parse the lines between the delimiters as TOML, with the `name`, `description`, `author` and
+/// `snipped` keys at the top and the arguments in `[args.Name]` tables.
+///
+/// Returns the first error, with the line it is on.
+pub fn parse(lines: &[(usize, String)]) -> Result<FrontMatter, Problem> {
+    let text = lines
+        .iter()
+        .map(|(_, line)| line.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    // source line of a byte of `text`
+    let line = |at: usize| {
+        let index = text[..at.min(text.len())]
+            .matches('\n')
+            .count();
+        lines
+            .get(index)
+            .map_or(0, |(number, _)| *number)
+    };
+
+    let document = toml::from_str::<Document>(&text).map_err(|e| {
+        (
+            line(e.span().map_or(0, |span| span.start)),
+            e.message().to_string(),
+        )
+    })?;
+
+    let mut args = IndexMap::new();
+    for (name, table) in document.args {
+        let table_line = line(table.span().start);
+        let table = table.into_inner();
+
+        let arg_type = match table.arg_type {
+            Some(arg_type) => Some(ArgType::parse(arg_type.get_ref()).ok_or_else(|| {
+                (
+                    line(arg_type.span().start),
+                    format!(
+                        "unknown argument type `{}`, expected one of {} or choices like `a|b`",
+                        arg_type.get_ref(),
+                        ArgType::NAMES.join(", ")
+                    ),
+                )
+            })?),
+            None => None,
+        };
+
+        let default = match table.default {
+            Some(default) => {
+                let default_line = line(default.span().start);
+                let value = match default.into_inner() {
+                    Value::String(value) => value,
+                    // numbers and booleans are typed as they are written
+                    value @ (Value::Integer(_) | Value::Float(_) | Value::Boolean(_)) => {
+                        value.to_string()
+                    },
+                    _ => {
+                        return Err((
+                            default_line,
+                            format!("default of `{name}` should be a string, number or boolean"),
+                        ))
+                    },
+                };
+                if let Some(arg_type) = &arg_type {
+                    arg_type
+                        .validate(&value)
+                        .map_err(|e| (default_line, format!("invalid default of `{name}`: {e}")))?;
+                }
+                Some(value)
+            },
+            None => None,
+        };
+
+        let help = match (table.help, table.example) {
+            (None, None) => None,
+            (description, example) => Some(ArgHelp {
+                description: description.unwrap_or_default(),
+                example,
+            }),
+        };
+
+        args.insert(
+            name,
+            Declaration {
+                default,
+                arg_type,
+                help,
+                line: table_line,
+            },
+        );
+    }
+
+    if let Some(required) = &document.snipped {
+        let snipped_line = line(required.span().start);
+        let required = required.get_ref();
+        let required_version =
+            version(required).ok_or((snipped_line, format!("invalid version `{required}`")))?;
+        if required_version > version(VERSION).unwrap_or_default() {
+            return Err((
+                snipped_line,
+                format!("the snippet needs snipped {required} or newer, this is {VERSION}"),
+            ));
+        }
+    }
+
+    Ok(FrontMatter {
+        metadata: Metadata {
+            name: document.name,
+            description: document.description,
+            author: document.author,
+            snipped: document
+                .snipped
+                .map(Spanned::into_inner),
+        },
+        args,
+    })
+}
+
+/// Numbers of a `major.minor.patch` version, `0.2` is the same as `0.2.0`.
+fn version(text: &str) -> Option<Vec<u64>> {
+    let mut numbers = text
+        .trim()
+        .split('.')
+        .map(|number| number.parse().ok())
+        .collect::<Option<Vec<u64>>>()?;
+    while numbers.last() == Some(&0) {
+        numbers.pop();
+    }
+    Some(numbers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let text = "\
+            name = \"Connect to the database\" # shown in `--list-args`\n\
+            author = 'Jane \\o/'\n\
+            description = \"\"\"\n\
+            Caf\\u00e9 database\"\"\"\n\
+            snipped = \"0.1\"\n\
+            \n\
+            [args.Host]\n\
+            type = \"hostname\"\n\
+            example = \"db1.example.com\"\n\
+            help = \"Database server\"\n\
+            [args.\"Port number\"]\n\
+            default = 5432";
+        let lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 2, line.to_string()))
+            .collect::<Vec<_>>();
+
+        let front_matter = parse(&lines).unwrap();
+        assert_eq!(
+            front_matter.metadata,
+            Metadata {
+                name: Some("Connect to the database".to_string()),
+                description: Some("Café database".to_string()),
+                author: Some("Jane \\o/".to_string()),
+                snipped: Some("0.1".to_string()),
+            }
+        );
+        assert_eq!(
+            front_matter
+                .args
+                .keys()
+                .collect::<Vec<_>>(),
+            ["Host", "Port number"]
+        );
+        assert_eq!(
+            front_matter.args["Host"].help,
+            Some(ArgHelp {
+                description: "Database server".to_string(),
+                example: Some("db1.example.com".to_string()),
+            })
+        );
+        assert_eq!(front_matter.args["Port number"].line, 12);
+        assert_eq!(
+            front_matter.args["Port number"].default,
+            Some("5432".to_string())
+        );
+
+        let error = |text: &str| {
+            let lines = text
+                .lines()
+                .enumerate()
+                .map(|(i, line)| (i + 2, line.to_string()))
+                .collect::<Vec<_>>();
+            parse(&lines).unwrap_err()
+        };
+        assert_eq!(error("snipped = \"999.0\"").0, 2);
+        assert_eq!(error("[args.Host]\ncolour = \"red\"").0, 3);
+        assert_eq!(error("name = \"a\"\n[args.Port number]").0, 3);
+        assert!(error("[args.Port]\ntype = \"port\"\ndefault = \"http\"")
+            .1
+            .contains("invalid default"));
+        assert!(error("[args.Port]\ndefault = [80]")
+            .1
+            .contains("string, number or boolean"));
+        assert!(error("[tools]").1.contains("unknown field"));
+    }
+}
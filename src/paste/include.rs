@@ -0,0 +1,234 @@
+use std::error::Error;
+use std::fs;
+
+use indexmap::IndexMap;
+
+use super::command;
+use super::parse;
+use super::parse::Snippet;
+use super::target;
+
+/// Load and parse the snippet, with the snippets it `##include`s spliced in.
+pub fn load(target: &str) -> Result<Snippet, Box<dyn Error>> {
+    resolve(target, &mut Vec::new(), &mut |target| {
+        Ok(parse::snippet(target::parse_and_load(target)?)?)
+    })
+}
+
+/// Splice in the includes of the snippet `load` gives for `target`, `stack` are the snippets
+/// including it.
+fn resolve(
+    target: &str,
+    stack: &mut Vec<(String, String)>,
+    load: &mut impl FnMut(&str) -> Result<Snippet, Box<dyn Error>>,
+) -> Result<Snippet, Box<dyn Error>> {
+    // the same file can be written differently, remote ones are compared as written
+    let identity = match target::is_remote(target) {
+        true => target.to_string(),
+        false => fs::canonicalize(target).map_or(target.to_string(), |path| {
+            path.to_string_lossy()
+                .into_owned()
+        }),
+    };
+    if let Some(start) = stack
+        .iter()
+        .position(|(included, _)| *included == identity)
+    {
+        let cycle = stack[start..]
+            .iter()
+            .map(|(_, target)| format!("`{target}`"))
+            .chain([format!("`{target}`")])
+            .collect::<Vec<_>>();
+        return Err(format!("Include cycle: {}", cycle.join(" -> ")).into());
+    }
+
+    let mut snippet = load(target)?;
+
+    // checked here so that local snippets cannot get around it by including remote ones
+    if let Some(command) = command::first(&snippet.lines) {
+        if target::is_remote(target) {
+            return Err(format!(
+                "Snippets from `github:`, `gist:` and URLs cannot run host commands \
+                (like `{command}`)"
+            )
+            .into());
+        }
+    }
+    if snippet
+        .includes
+        .is_empty()
+    {
+        let unused = parse::unused_declarations(&snippet.lines, &snippet.declared);
+        snippet
+            .diagnostics
+            .extend(unused);
+        return Ok(snippet);
+    }
+
+    stack.push((identity, target.to_string()));
+    let mut included = Vec::new();
+    for (index, include) in &snippet.includes {
+        let joined = target::join(target, include)?;
+        // checked on the joined target, as some local ones (like `C:file` on Windows) look like
+        // relative paths or URLs
+        if target::is_remote(target) && !target::is_remote(&joined) {
+            return Err(target::Error::IncludeFromRemote(joined).into());
+        }
+        let nested = resolve(&joined, stack, load)
+            .map_err(|e| format!("Could not include `{include}` in `{target}`: {e}"))?;
+        included.push((*index, joined, nested));
+    }
+    stack.pop();
+
+    // included declarations come first, so that the including snippet can override them
+    let mut defaults = IndexMap::new();
+    let mut types = IndexMap::new();
+    let mut help = IndexMap::new();
+    let mut diagnostics = Vec::new();
+    // while the arguments the including snippet declares are asked first
+    let mut declared = snippet.declared;
+    let own = declared.len();
+    for (_, joined, nested) in &included {
+        defaults.extend(nested.defaults.clone());
+        types.extend(nested.types.clone());
+        help.extend(nested.help.clone());
+        diagnostics.extend(
+            nested
+                .diagnostics
+                .iter()
+                .map(|diagnostic| {
+                    let mut diagnostic = diagnostic.clone();
+                    diagnostic.message = format!("In `{joined}`: {}", diagnostic.message);
+                    diagnostic
+                }),
+        );
+        for (name, token) in &nested.declared {
+            if !declared
+                .iter()
+                .any(|(declared, _)| declared == name)
+            {
+                declared.push((name.clone(), token.clone()));
+            }
+        }
+    }
+    defaults.extend(snippet.defaults);
+    types.extend(snippet.types);
+    help.extend(snippet.help);
+    diagnostics.extend(snippet.diagnostics);
+
+    // spliced from the last, so the indices of the earlier ones stay the same
+    for (index, _, nested) in included
+        .into_iter()
+        .rev()
+    {
+        // included lines are reported at the include line
+        let number = snippet.line_numbers[index - 1];
+        snippet
+            .line_numbers
+            .splice(index..index, vec![number; nested.lines.len()]);
+        snippet
+            .lines
+            .splice(index..index, nested.lines);
+    }
+    // only the own declarations, the included snippets checked theirs
+    diagnostics.extend(parse::unused_declarations(
+        &snippet.lines,
+        &declared[..own],
+    ));
+
+    Ok(Snippet {
+        declared,
+        defaults,
+        types,
+        help,
+        includes: Vec::new(),
+        diagnostics,
+        ..snippet
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paste::parse::LinePart;
+
+    #[test]
+    fn test_resolve() {
+        let sources = [
+            ("main", "##include proxy\nssh $@host$\n##include login"),
+            ("proxy", "export http_proxy=$@proxy::proxy:3128$"),
+            ("login", "##include proxy\nlogin $@user::root$"),
+            ("loop", "##include again"),
+            ("again", "##include loop"),
+            ("https://example.com/drive", "##include C:x"),
+            ("https://example.com/file", "##include file:/etc/passwd"),
+            (
+                "deploy",
+                "+++\n[args.user]\n[args.spare]\n+++\n##include login\ndeploy $@host$",
+            ),
+        ];
+        let mut load = |target: &str| -> Result<Snippet, Box<dyn Error>> {
+            let (_, source) = sources
+                .iter()
+                .find(|(name, _)| *name == target)
+                .ok_or("not found")?;
+            Ok(parse::snippet(Box::new(source.as_bytes()))?)
+        };
+
+        let snippet = resolve("main", &mut Vec::new(), &mut load).unwrap();
+        let texts = snippet
+            .lines
+            .iter()
+            .map(|line| match &line[0] {
+                LinePart::Text(text) => text.as_str(),
+                _ => "",
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            [
+                "##include proxy",
+                "export http_proxy=",
+                "ssh ",
+                "##include login",
+                "##include proxy",
+                "export http_proxy=",
+                "login ",
+            ]
+        );
+        assert_eq!(snippet.line_numbers, [1, 1, 2, 3, 3, 3, 3]);
+        assert_eq!(snippet.defaults["proxy"], "proxy:3128");
+        assert_eq!(snippet.defaults["user"], "root");
+
+        // `user` is only used in the included snippet, but still declared first
+        let snippet = resolve("deploy", &mut Vec::new(), &mut load).unwrap();
+        assert_eq!(
+            snippet
+                .declared
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            ["user", "spare"]
+        );
+        assert_eq!(snippet.diagnostics.len(), 1);
+        assert!(snippet.diagnostics[0]
+            .message
+            .contains("`spare`"));
+
+        for remote in ["https://example.com/drive", "https://example.com/file"] {
+            let error = resolve(remote, &mut Vec::new(), &mut load)
+                .err()
+                .unwrap();
+            assert!(error
+                .to_string()
+                .starts_with("Snippets from the internet cannot include local file"));
+        }
+
+        let error = resolve("loop", &mut Vec::new(), &mut load)
+            .err()
+            .unwrap();
+        assert!(error
+            .to_string()
+            .ends_with("Include cycle: `loop` -> `again` -> `loop`"));
+    }
+}
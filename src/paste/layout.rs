@@ -0,0 +1,526 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+/// A key on the keyboard, no matter what the layout says it types.
+///
+/// Identified by its Linux evdev code, which is the same as the PC set 1 scancode for the keys used
+/// here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PhysicalKey(pub u16);
+
+impl PhysicalKey {
+    pub const TAB: Self = Self(15);
+    pub const SHIFT: Self = Self(42);
+    pub const SPACE: Self = Self(57);
+    pub const ALTGR: Self = Self(100);
+
+    /// Keycode for the current platform, as expected by `enigo::Keyboard::raw`.
+    #[cfg(target_os = "windows")]
+    pub fn raw(self) -> u16 {
+        match self {
+            // right alt is an extended key (`E0 38`), enigo expects those to have bits over `7F`
+            Self::ALTGR => 0x38 | 0x80,
+            Self(code) => code,
+        }
+    }
+
+    /// Keycode for the current platform, as expected by `enigo::Keyboard::raw`.
+    #[cfg(target_os = "macos")]
+    pub fn raw(self) -> u16 {
+        MACOS_CODES
+            .iter()
+            .find(|(code, _)| *code == self.0)
+            .map(|(_, mac)| *mac)
+            .unwrap_or(0xFFFF)
+    }
+
+    /// Keycode for the current platform, as expected by `enigo::Keyboard::raw`.
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    pub fn raw(self) -> u16 {
+        // X11 keycodes are offset from evdev ones
+        self.0 + 8
+    }
+
+    /// Name of the key, which is the character it types on the US layout where possible.
+    pub fn name(self) -> String {
+        match self {
+            Self::TAB => "tab".to_string(),
+            Self::SHIFT => "shift".to_string(),
+            Self::SPACE => "space".to_string(),
+            Self::ALTGR => "altgr".to_string(),
+            Self(86) => "iso".to_string(),
+            key => KEY_ROWS
+                .iter()
+                .zip(US[0])
+                .find_map(|(keys, chars)| {
+                    keys.iter()
+                        .zip(chars.chars())
+                        .find(|(code, _)| **code == key.0)
+                        .map(|(_, ch)| ch.to_string())
+                })
+                .unwrap_or_else(|| format!("#{}", key.0)),
+        }
+    }
+
+    /// Inverse of `name`, also accepting `#<code>` for any key.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let key = match name {
+            "tab" => Self::TAB,
+            "shift" => Self::SHIFT,
+            "space" => Self::SPACE,
+            "altgr" => Self::ALTGR,
+            "iso" => Self(86),
+            name if name.starts_with('#') => Self(name[1..].parse().ok()?),
+            name => {
+                let mut chars = name.chars();
+                let ch = chars.next()?;
+                if chars
+                    .next()
+                    .is_some()
+                {
+                    return None;
+                }
+
+                KEY_ROWS
+                    .iter()
+                    .zip(US[0])
+                    .find_map(|(keys, row)| {
+                        keys.iter()
+                            .zip(row.chars())
+                            .find(|(_, x)| *x == ch && ch != ' ')
+                            .map(|(code, _)| Self(*code))
+                    })?
+            },
+        };
+        Some(key)
+    }
+}
+
+impl Display for PhysicalKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { write!(f, "{}", self.name()) }
+}
+
+/// Keys of the main keyboard block, row by row (the first key of the last row is the extra ISO key
+/// next to left shift).
+const KEY_ROWS: [&[u16]; 4] = [
+    &[41, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13],
+    &[16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 43],
+    &[30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40],
+    &[86, 44, 45, 46, 47, 48, 49, 50, 51, 52, 53],
+];
+
+#[cfg(target_os = "macos")]
+const MACOS_CODES: &[(u16, u16)] = &[
+    (41, 0x32),
+    (2, 0x12),
+    (3, 0x13),
+    (4, 0x14),
+    (5, 0x15),
+    (6, 0x17),
+    (7, 0x16),
+    (8, 0x1A),
+    (9, 0x1C),
+    (10, 0x19),
+    (11, 0x1D),
+    (12, 0x1B),
+    (13, 0x18),
+    (16, 0x0C),
+    (17, 0x0D),
+    (18, 0x0E),
+    (19, 0x0F),
+    (20, 0x11),
+    (21, 0x10),
+    (22, 0x20),
+    (23, 0x22),
+    (24, 0x1F),
+    (25, 0x23),
+    (26, 0x21),
+    (27, 0x1E),
+    (43, 0x2A),
+    (30, 0x00),
+    (31, 0x01),
+    (32, 0x02),
+    (33, 0x03),
+    (34, 0x05),
+    (35, 0x04),
+    (36, 0x26),
+    (37, 0x28),
+    (38, 0x25),
+    (39, 0x29),
+    (40, 0x27),
+    (86, 0x0A),
+    (44, 0x06),
+    (45, 0x07),
+    (46, 0x08),
+    (47, 0x09),
+    (48, 0x0B),
+    (49, 0x2D),
+    (50, 0x2E),
+    (51, 0x2B),
+    (52, 0x2F),
+    (53, 0x2C),
+    (15, 0x30),
+    (42, 0x38),
+    (57, 0x31),
+    (100, 0x3D),
+];
+
+/// Characters typed by `KEY_ROWS`, for plain, `Shift` and `AltGr` presses (space is no character).
+type Levels = [[&'static str; 4]; 3];
+
+const US: Levels = [
+    ["`1234567890-=", "qwertyuiop[]\\", "asdfghjkl;'", " zxcvbnm,./"],
+    ["~!@#$%^&*()_+", "QWERTYUIOP{}|", "ASDFGHJKL:\"", " ZXCVBNM<>?"],
+    ["             ", "             ", "           ", "           "],
+];
+
+const DE: Levels = [
+    [" 1234567890ß ", "qwertzuiopü+#", "asdfghjklöä", "<yxcvbnm,.-"],
+    ["°!\"§$%&/()=? ", "QWERTZUIOPÜ*'", "ASDFGHJKLÖÄ", ">YXCVBNM;:_"],
+    ["  ²³   {[]}\\ ", "@ €        ~ ", "           ", "|      µ   "],
+];
+
+/// Dead keys, in the layout file format.
+const DE_DEAD: &str = "\
+    ^ ` space\n\
+    ´ = space\n\
+    ` shift+= space\n\
+    â ` a\n\
+    ê ` e\n\
+    î ` i\n\
+    ô ` o\n\
+    û ` u\n\
+    Â ` shift+a\n\
+    Ê ` shift+e\n\
+    Î ` shift+i\n\
+    Ô ` shift+o\n\
+    Û ` shift+u\n\
+    á = a\n\
+    é = e\n\
+    í = i\n\
+    ó = o\n\
+    ú = u\n\
+    Á = shift+a\n\
+    É = shift+e\n\
+    Í = shift+i\n\
+    Ó = shift+o\n\
+    Ú = shift+u\n\
+    à shift+= a\n\
+    è shift+= e\n\
+    ì shift+= i\n\
+    ò shift+= o\n\
+    ù shift+= u\n\
+    À shift+= shift+a\n\
+    È shift+= shift+e\n\
+    Ì shift+= shift+i\n\
+    Ò shift+= shift+o\n\
+    Ù shift+= shift+u\n";
+
+const RU: Levels = [
+    ["ё1234567890-=", "йцукенгшщзхъ\\", "фывапролджэ", "\\ячсмитьбю."],
+    ["Ё!\"№;%:?*()_+", "ЙЦУКЕНГШЩЗХЪ/", "ФЫВАПРОЛДЖЭ", "/ЯЧСМИТЬБЮ,"],
+    ["             ", "             ", "           ", "           "],
+];
+
+/// Key to press for a character, together with modifiers to hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stroke {
+    pub key: PhysicalKey,
+    pub shift: bool,
+    pub altgr: bool,
+}
+
+impl Stroke {
+    /// Parse `[shift+][altgr+]<key>`.
+    fn parse(text: &str) -> Option<Self> {
+        let mut stroke = Stroke {
+            key: PhysicalKey(0),
+            shift: false,
+            altgr: false,
+        };
+
+        let mut rest = text;
+        while let Some((modifier, after)) = rest.split_once('+') {
+            match modifier {
+                "shift" => stroke.shift = true,
+                "altgr" => stroke.altgr = true,
+                _ => return None,
+            }
+            rest = after;
+        }
+
+        stroke.key = PhysicalKey::from_name(rest)?;
+        Some(stroke)
+    }
+}
+
+/// Keyboard layout of the target, used to type characters as physical key presses.
+///
+/// A character may take multiple strokes, like pressing a dead key before the letter.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    strokes: HashMap<char, Vec<Stroke>>,
+}
+
+impl Layout {
+    pub const BUILTIN: [&'static str; 3] = ["us", "de", "ru"];
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "us" => Some(Self::from_levels(&US)),
+            "de" => {
+                let mut layout = Self::from_levels(&DE);
+                layout
+                    .extend(DE_DEAD)
+                    .expect("built-in dead keys should be valid");
+                Some(layout)
+            },
+            "ru" => Some(Self::from_levels(&RU)),
+            _ => None,
+        }
+    }
+
+    /// Parse a layout file.
+    ///
+    /// Every line maps a character (or `space`, `tab`, `U+<hex>`) to one or more strokes of
+    /// `[shift+][altgr+]<key>`, separated by whitespace. Keys are named after the characters they
+    /// type on the US layout, or `space`, `tab`, `iso` (the key next to left shift on ISO
+    /// keyboards) and `#<evdev code>`. An `extends <built-in layout>` line starts from a built-in layout.
+    /// Lines starting with `#` are comments (so `#` itself is written as `U+23`).
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let mut layout = Self {
+            strokes: HashMap::new(),
+        };
+        layout.extend(text)?;
+        Ok(layout)
+    }
+
+    fn extend(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+        for (i, line) in text
+            .lines()
+            .enumerate()
+        {
+            let mut tokens = line.split_whitespace();
+            let Some(first) = tokens.next() else {
+                continue;
+            };
+
+            let ch = match first {
+                comment if comment.starts_with('#') => continue,
+                "extends" => {
+                    let name = tokens
+                        .next()
+                        .unwrap_or_default();
+                    let base = Self::builtin(name)
+                        .ok_or_else(|| format!("Line {}: Unknown layout `{name}`", i + 1))?;
+                    self.strokes
+                        .extend(base.strokes);
+                    continue;
+                },
+                "space" => ' ',
+                "tab" => '\t',
+                code if code.starts_with("U+") => u32::from_str_radix(&code[2..], 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| format!("Line {}: Invalid character `{code}`", i + 1))?,
+                single => {
+                    let mut chars = single.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(ch), None) => ch,
+                        _ =>
+                            return Err(format!(
+                                "Line {}: Invalid character `{single}`",
+                                i + 1
+                            )
+                            .into()),
+                    }
+                },
+            };
+
+            let mut strokes = Vec::new();
+            for token in tokens {
+                strokes.push(
+                    Stroke::parse(token)
+                        .ok_or_else(|| format!("Line {}: Invalid stroke `{token}`", i + 1))?,
+                );
+            }
+            if strokes.is_empty() {
+                return Err(format!("Line {}: No strokes for `{first}`", i + 1).into());
+            }
+
+            self.strokes
+                .insert(ch, strokes);
+        }
+        Ok(())
+    }
+
+    fn from_levels(levels: &Levels) -> Self {
+        // inserted first, so that spaces used as gaps in `levels` do not override it
+        let mut strokes = HashMap::new();
+        strokes.insert(
+            ' ',
+            vec![Stroke {
+                key: PhysicalKey::SPACE,
+                shift: false,
+                altgr: false,
+            }],
+        );
+        strokes.insert(
+            '\t',
+            vec![Stroke {
+                key: PhysicalKey::TAB,
+                shift: false,
+                altgr: false,
+            }],
+        );
+
+        for (level, rows) in levels
+            .iter()
+            .enumerate()
+        {
+            for (row, keys) in rows
+                .iter()
+                .zip(KEY_ROWS)
+            {
+                for (ch, code) in row
+                    .chars()
+                    .zip(keys)
+                {
+                    // first (simplest) stroke wins for characters on multiple keys
+                    strokes
+                        .entry(ch)
+                        .or_insert(vec![Stroke {
+                            key: PhysicalKey(*code),
+                            shift: level == 1,
+                            altgr: level == 2,
+                        }]);
+                }
+            }
+        }
+
+        Self { strokes }
+    }
+
+    pub fn strokes(&self, ch: char) -> Option<&[Stroke]> {
+        self.strokes
+            .get(&ch)
+            .map(Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_levels() {
+        for levels in [US, DE, RU] {
+            for rows in levels {
+                for (row, keys) in rows
+                    .iter()
+                    .zip(KEY_ROWS)
+                {
+                    assert_eq!(
+                        row.chars()
+                            .count(),
+                        keys.len(),
+                        "{row}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_builtin_strokes() {
+        let de = Layout::builtin("de").unwrap();
+        assert_eq!(
+            de.strokes('z'),
+            Some(
+                &[Stroke {
+                    key: PhysicalKey(21),
+                    shift: false,
+                    altgr: false,
+                }][..]
+            )
+        );
+        assert_eq!(
+            de.strokes('@'),
+            Some(
+                &[Stroke {
+                    key: PhysicalKey(16),
+                    shift: false,
+                    altgr: true,
+                }][..]
+            )
+        );
+        assert_eq!(
+            de.strokes('^'),
+            Some(
+                &[
+                    Stroke {
+                        key: PhysicalKey(41),
+                        shift: false,
+                        altgr: false,
+                    },
+                    Stroke {
+                        key: PhysicalKey::SPACE,
+                        shift: false,
+                        altgr: false,
+                    }
+                ][..]
+            )
+        );
+        assert_eq!(PhysicalKey(21).name(), "y");
+    }
+
+    #[test]
+    fn test_parse() {
+        let layout = Layout::parse(
+            "\
+            # swap y and z\n\
+            extends us\n\
+            y z\n\
+            z y\n\
+            # shift+altgr+#30\n\
+            U+E9 ' shift+altgr+#30\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            layout.strokes('y'),
+            Some(
+                &[Stroke {
+                    key: PhysicalKey(44),
+                    shift: false,
+                    altgr: false,
+                }][..]
+            )
+        );
+        assert_eq!(
+            layout.strokes('é'),
+            Some(
+                &[
+                    Stroke {
+                        key: PhysicalKey(40),
+                        shift: false,
+                        altgr: false,
+                    },
+                    Stroke {
+                        key: PhysicalKey(30),
+                        shift: true,
+                        altgr: true,
+                    }
+                ][..]
+            )
+        );
+        assert!(layout
+            .strokes('Y')
+            .is_some());
+        assert!(Layout::parse("y nokey").is_err());
+        assert!(Layout::parse("yy z").is_err());
+    }
+}
@@ -1,78 +1,525 @@
-mod parse;
+pub(crate) mod arg_type;
+mod backend;
+mod command;
+mod condition;
+mod environment;
+pub(crate) mod filter;
+mod focus;
+mod front_matter;
+mod include;
+mod layout;
+mod output;
+pub(crate) mod parse;
+pub(crate) mod syntax;
 mod target;
 
+use std::env;
 use std::error::Error;
+use std::fs;
 use std::io;
-use std::thread::sleep;
+use std::io::stdout;
 use std::time::Duration;
 
 use enigo::Direction::*;
 use enigo::Enigo;
-use enigo::InputResult;
 use enigo::Key;
-use enigo::Keyboard;
 use enigo::Settings;
 use indexmap::IndexMap;
+use log::warn;
 
+use self::arg_type::ArgType;
+use self::backend::Backend;
+use self::backend::Preview;
+use self::backend::Recorder;
+use self::condition::Conditions;
+use self::focus::Focus;
+use self::focus::FOCUS_VAR;
+use self::front_matter::Metadata;
+use self::layout::Layout;
+use self::layout::PhysicalKey;
+use self::output::Output;
+use self::parse::ArgHelp;
+use self::parse::LineEnd;
 use self::parse::LinePart;
+use self::parse::Setting;
 use crate::ask;
+use crate::ask_line;
+use crate::secret;
+use crate::secret::Zeroing;
+
+/// How lines are typed, changeable by `$~` settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Typing {
+    char_delay: Duration,
+    line_delay: Duration,
+    line_end: LineEnd,
+    /// Pressed after the last line instead of `line_end`, if set.
+    final_line_end: Option<LineEnd>,
+}
+
+impl Default for Typing {
+    fn default() -> Self {
+        Self {
+            char_delay: Duration::from_millis(10),
+            line_delay: Duration::from_millis(100),
+            line_end: LineEnd::Keys(vec![Key::Return]),
+            final_line_end: None,
+        }
+    }
+}
+
+impl Typing {
+    /// Apply `$~` settings, restoring values from `run` where they are set to `default`.
+    fn apply(&mut self, settings: &[Setting], run: &Typing) {
+        for setting in settings {
+            match setting {
+                Setting::CharDelay(delay) =>
+                    self.char_delay = delay.map_or(run.char_delay, Duration::from_millis),
+                Setting::LineDelay(delay) =>
+                    self.line_delay = delay.map_or(run.line_delay, Duration::from_millis),
+                Setting::LineEnd(line_end) =>
+                    self.line_end = line_end
+                        .clone()
+                        .unwrap_or_else(|| {
+                            run.line_end
+                                .clone()
+                        }),
+                Setting::FinalLineEnd(line_end) =>
+                    self.final_line_end = line_end
+                        .clone()
+                        .or_else(|| {
+                            run.final_line_end
+                                .clone()
+                        }),
+            }
+        }
+    }
+}
 
 pub fn main(args: impl IntoIterator<Item = String>) -> Result<(), Box<dyn Error>> {
-    let mut args = args.into_iter();
+    let mut args = args
+        .into_iter()
+        .peekable();
 
-    let snippet = target::parse_and_load(&match args.next() {
+    let mut output = None;
+    let mut record = None;
+    let mut dry_run = false;
+    let mut list = false;
+    let mut allow_commands = false;
+    let mut strict = false;
+    let mut processing = true;
+    let mut typing = Typing::default();
+    let mut focus = None;
+    let mut layout = None;
+    while let Some(arg) = args.next_if(|arg| arg.starts_with('-') && arg != "-") {
+        match (&*arg, arg.split_once('=')) {
+            ("-n" | "--no-processing", _) => processing = false,
+            ("--dry-run", _) => dry_run = true,
+            ("--list-args", _) => list = true,
+            ("--allow-commands", _) => allow_commands = true,
+            ("--strict", _) => strict = true,
+            (_, Some(("-o" | "--output", target))) => output = Some(Output::parse(target)),
+            (_, Some(("--record", target))) => record = Some(Output::parse(target)),
+            (_, Some(("--char-delay", delay))) => typing.char_delay = parse_millis(&arg, delay)?,
+            (_, Some(("--line-delay", delay))) => typing.line_delay = parse_millis(&arg, delay)?,
+            (_, Some(("--eol", line_end))) => typing.line_end = parse::line_end(line_end)?,
+            (_, Some(("--final-eol", line_end))) =>
+                typing.final_line_end = Some(parse::line_end(line_end)?),
+            (_, Some(("--focus", strategy))) => focus = Some(Focus::parse(strategy)?),
+            (_, Some(("--layout", name))) => layout = Some(parse_layout(name)?),
+            _ => return Err(format!("Unknown option: {arg}").into()),
+        }
+    }
+
+    let target = match args.next() {
         Some(x) => x,
-        None => ask("Target snippet (supports `github:`, `gist:`, `http(s)://`, file)")?,
-    })?;
+        None => ask("Target snippet (supports `github:`, `gist:`, `http(s)://`, `-`, `!`, file)")?,
+    };
+    let parse::Snippet {
+        metadata,
+        declared,
+        mut lines,
+        defaults,
+        types,
+        help,
+        diagnostics,
+        ..
+    } = match processing {
+        true => include::load(&target)?,
+        false => parse::raw(target::parse_and_load(&target)?)?,
+    };
+
+    for diagnostic in &diagnostics {
+        warn!("{diagnostic}");
+    }
+    if strict && !diagnostics.is_empty() {
+        return Err(format!(
+            "Snippet has {} warning(s), not typing it because of `--strict`",
+            diagnostics.len()
+        )
+        .into());
+    }
 
-    let parse::Snippet { lines, defaults } = parse::snippet(snippet)?;
+    let order = declared
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>();
+    let names = arg_names(&lines, &order);
+    if list {
+        list_args(&metadata, &names, &defaults, &types, &help);
+        return Ok(());
+    }
 
-    let mut arg_values = IndexMap::<String, String>::new();
-    pull_args_from_argv(args, &mut arg_values);
+    // commands run on the host, so they have to be allowed (remote snippets are refused on load)
+    if let Some(command) = command::first(&lines) {
+        if !allow_commands {
+            return Err(format!(
+                "The snippet runs host commands (like `{command}`), \
+                allow them with `--allow-commands`"
+            )
+            .into());
+        }
+    }
 
-    for line in &lines {
-        while let Some(arg) = missing_line_arg(&line, &arg_values) {
-            ask_arg(
-                arg,
-                &defaults,
-                &mut arg_values,
-            )?;
+    // values are zeroed once they are no longer needed, in case some of them are secret
+    let mut arg_values = Zeroing(IndexMap::<String, String>::new());
+    let mut arglist_values = Zeroing(IndexMap::<String, Vec<String>>::new());
+    pull_args_from_argv(
+        args,
+        &mut arg_values,
+        &mut arglist_values,
+    );
+    for (arg, values) in arglist_values.iter() {
+        if is_secret(&types, arg) {
+            warn!(
+                "Secret argument `{arg}` was given on the command line, \
+                it may be kept in the shell history"
+            );
+        }
+        if let Some(arg_type) = types.get(arg) {
+            for value in values {
+                arg_type
+                    .validate(value)
+                    .map_err(|e| format!("Invalid value for `{arg}`: {e}"))?;
+            }
         }
     }
 
+    ask_missing_args(
+        &lines,
+        &order,
+        &defaults,
+        &types,
+        &help,
+        &mut arg_values,
+        &mut arglist_values,
+    )?;
+
+    if processing {
+        lines = condition::resolve(lines, &arg_values);
+        // comments are only there to ask their arguments, they are not typed
+        lines.retain(|line| !parse::is_comment(line));
+        // only the variables that are typed have to be set
+        lines = environment::resolve(lines, |name| env::var(name).ok())?;
+    }
+
+    // commands only run when typing, everything else shows their outputs masked
+    let typing_keys = output.is_none() && !dry_run && record.is_none();
+    let outputs = match typing_keys {
+        true => command::run_all(&lines, command::run)?,
+        false => Zeroing(IndexMap::new()),
+    };
+    let shown_outputs = command::masked(&lines);
+
+    // secret values are only ever typed, everything else shows them masked
+    let (shown_values, shown_list_values) = masked(
+        &types,
+        &arg_values,
+        &arglist_values,
+    );
+
+    if let Some(output) = output {
+        return output.write(&output::render(
+            &lines,
+            &shown_values,
+            &shown_list_values,
+            &shown_outputs,
+        ));
+    }
+
+    if let Some(layout) = &layout {
+        check_layout(
+            layout,
+            &lines,
+            &arg_values,
+            &arglist_values,
+            &outputs,
+            &types,
+        )?;
+    }
+
+    // resolved before the dry run, which previews the switch too
+    let focus = match (focus, env::var(FOCUS_VAR)) {
+        (Some(focus), _) => focus,
+        (None, Ok(strategy)) => Focus::parse(&strategy)?,
+        (None, Err(_)) => Focus::default(),
+    };
+
+    if dry_run {
+        describe_args(
+            &names,
+            &help,
+            &shown_values,
+            &shown_list_values,
+        );
+        let mut preview = Preview::new(stdout());
+        focus.switch(&mut preview)?;
+        type_lines(
+            &mut preview,
+            &lines,
+            &shown_values,
+            &shown_list_values,
+            &shown_outputs,
+            &typing,
+            layout.as_ref(),
+        )?;
+        return preview.finish();
+    }
+
+    if let Some(record) = record {
+        let mut recorder = Recorder(Vec::new());
+        type_lines(
+            &mut recorder,
+            &lines,
+            &shown_values,
+            &shown_list_values,
+            &shown_outputs,
+            &typing,
+            layout.as_ref(),
+        )?;
+        return record.write(&String::from_utf8_lossy(&recorder.0));
+    }
+
     let mut enigo = Enigo::new(&Settings::default())?;
-    enigo.key(Key::Alt, Press)?;
-    enigo.key(Key::Tab, Press)?;
-    enigo.key(Key::Tab, Release)?;
-    enigo.key(Key::Alt, Release)?;
+    focus.switch(&mut enigo)?;
 
-    for line in lines {
-        sleep(Duration::from_millis(100));
+    type_lines(
+        &mut enigo,
+        &lines,
+        &arg_values,
+        &arglist_values,
+        &outputs,
+        &typing,
+        layout.as_ref(),
+    )
+}
+
+/// Built-in layout name or a path to a layout file.
+fn parse_layout(name: &str) -> Result<Layout, Box<dyn Error>> {
+    if let Some(layout) = Layout::builtin(name) {
+        return Ok(layout);
+    }
+
+    let text = fs::read_to_string(name).map_err(|e| {
+        format!(
+            "Layout `{name}` is neither one of {} nor a readable file ({e})",
+            Layout::BUILTIN.join(", ")
+        )
+    })?;
+    Layout::parse(&text).map_err(|e| format!("Layout `{name}`: {e}").into())
+}
 
-        if let Some(LinePart::Text(first)) = line.get(0) {
-            if first.starts_with("##") {
+/// Fail before typing anything if some character cannot be typed with the layout.
+fn check_layout(
+    layout: &Layout,
+    lines: &[Vec<LinePart>],
+    arg_values: &IndexMap<String, String>,
+    arglist_values: &IndexMap<String, Vec<String>>,
+    outputs: &IndexMap<String, String>,
+    types: &IndexMap<String, ArgType>,
+) -> Result<(), Box<dyn Error>> {
+    for part in lines
+        .iter()
+        .flatten()
+    {
+        // filters can add characters, so the values are checked as they are typed
+        let (texts, secret) = match part {
+            LinePart::Text(text) => (vec![Zeroing(text.clone())], None),
+            LinePart::Arg(arg, filters) => (
+                vec![filter::apply(filters, &arg_values[arg])],
+                Some(format!("Secret argument `{arg}`")).filter(|_| is_secret(types, arg)),
+            ),
+            LinePart::ArgList(arg, filters) => (
+                arglist_values[arg]
+                    .iter()
+                    .map(|value| filter::apply(filters, value))
+                    .collect(),
+                Some(format!("Secret argument `{arg}`")).filter(|_| is_secret(types, arg)),
+            ),
+            // outputs are only there if the commands are run
+            LinePart::Command(command) => match outputs.get(command) {
+                Some(output) => (
+                    vec![Zeroing(output.clone())],
+                    Some(format!("Output of command `{command}`")),
+                ),
+                None => continue,
+            },
+            _ => continue,
+        };
+
+        for ch in texts
+            .iter()
+            .flat_map(|text| text.chars())
+        {
+            if layout
+                .strokes(ch)
+                .is_some()
+            {
                 continue;
             }
+            return Err(match secret {
+                Some(secret) => format!("{secret} cannot be typed with the layout"),
+                None => format!("Character `{ch}` cannot be typed with the layout"),
+            }
+            .into());
         }
+    }
+    Ok(())
+}
 
-        for part in line {
-            match &part {
-                LinePart::Text(text) => type_text(&mut enigo, text)?,
-                LinePart::Delay(delay) => sleep(Duration::from_millis(*delay)),
-                LinePart::Arg(arg) => type_text(&mut enigo, &arg_values[arg])?,
-                LinePart::KeyCombo(keys) => key_combo(&mut enigo, keys)?,
+fn is_secret(types: &IndexMap<String, ArgType>, arg: &str) -> bool {
+    types.get(arg) == Some(&ArgType::Secret)
+}
+
+/// Copies of the values with the secret ones replaced by a mask.
+fn masked(
+    types: &IndexMap<String, ArgType>,
+    arg_values: &IndexMap<String, String>,
+    arglist_values: &IndexMap<String, Vec<String>>,
+) -> (IndexMap<String, String>, IndexMap<String, Vec<String>>) {
+    let mask = |arg: &str, value: &String| match is_secret(types, arg) {
+        true => secret::MASK.to_string(),
+        false => value.clone(),
+    };
+
+    let shown_values = arg_values
+        .iter()
+        .map(|(arg, value)| (arg.clone(), mask(arg, value)))
+        .collect();
+    let shown_list_values = arglist_values
+        .iter()
+        .map(|(arg, values)| {
+            (
+                arg.clone(),
+                values
+                    .iter()
+                    .map(|value| mask(arg, value))
+                    .collect(),
+            )
+        })
+        .collect();
+    (shown_values, shown_list_values)
+}
+
+fn parse_millis(option: &str, value: &str) -> Result<Duration, Box<dyn Error>> {
+    match value.parse() {
+        Ok(millis) => Ok(Duration::from_millis(millis)),
+        Err(_) => Err(format!("Expected milliseconds in `{option}`").into()),
+    }
+}
+
+fn type_lines(
+    backend: &mut dyn Backend,
+    lines: &[Vec<LinePart>],
+    arg_values: &IndexMap<String, String>,
+    arglist_values: &IndexMap<String, Vec<String>>,
+    outputs: &IndexMap<String, String>,
+    typing: &Typing,
+    layout: Option<&Layout>,
+) -> Result<(), Box<dyn Error>> {
+    let mut current = typing.clone();
+
+    let last_line = lines
+        .iter()
+        .rposition(|line| {
+            !parse::is_settings_only(line) && line_repeats(line, arglist_values) > 0
+        });
+
+    for (n, line) in lines
+        .iter()
+        .enumerate()
+    {
+        let submit = !parse::is_settings_only(line);
+        let repeats = line_repeats(line, arglist_values);
+
+        for i in 0..repeats {
+            if submit {
+                backend.delay(current.line_delay)?;
             }
-        }
 
-        enigo.key(Key::Return, Click)?;
+            for part in line {
+                let char_delay = current.char_delay;
+                match part {
+                    LinePart::Text(text) => type_text(
+                        backend,
+                        text,
+                        char_delay,
+                        layout,
+                    )?,
+                    LinePart::Delay(delay) => backend.delay(Duration::from_millis(*delay))?,
+                    LinePart::Arg(arg, filters) => type_text(
+                        backend,
+                        &filter::apply(filters, &arg_values[arg]),
+                        char_delay,
+                        layout,
+                    )?,
+                    LinePart::ArgList(arg, filters) => type_text(
+                        backend,
+                        &filter::apply(
+                            filters,
+                            arglist_value(&arglist_values[arg], i),
+                        ),
+                        char_delay,
+                        layout,
+                    )?,
+                    LinePart::KeyCombo(keys) => key_combo(backend, keys)?,
+                    LinePart::Settings(settings) => current.apply(settings, typing),
+                    LinePart::Command(command) => type_text(
+                        backend,
+                        &outputs[command],
+                        char_delay,
+                        layout,
+                    )?,
+                    // conditions and environment variables are resolved before typing
+                    LinePart::If(_) | LinePart::EndIf | LinePart::Env(_) => {},
+                }
+            }
+
+            if submit {
+                let line_end = match &current.final_line_end {
+                    Some(final_line_end) if Some(n) == last_line && i + 1 == repeats =>
+                        final_line_end,
+                    _ => &current.line_end,
+                };
+                match line_end {
+                    LineEnd::Nothing => {},
+                    LineEnd::Keys(keys) => match keys.as_slice() {
+                        [key] => backend.key(*key, Click)?,
+                        _ => key_combo(backend, keys)?,
+                    },
+                }
+            }
+        }
     }
 
     Ok(())
 }
 
+/// Repeating a key fills an arglist with multiple values, while an arg takes the last one.
 fn pull_args_from_argv(
     args: impl IntoIterator<Item = String>,
     arg_values: &mut IndexMap<String, String>,
+    arglist_values: &mut IndexMap<String, Vec<String>>,
 ) {
     for (a, b) in args
         .into_iter()
@@ -81,53 +528,360 @@ fn pull_args_from_argv(
         .map(|a| (&a[0], &a[1]))
     {
         arg_values.insert(a.to_string(), b.to_string());
+        arglist_values
+            .entry(a.to_string())
+            .or_default()
+            .push(b.to_string());
+    }
+}
+
+/// Ask the arguments that are declared in the front matter in the declared order (`order`), and
+/// then the arguments and arglists of every line, in order of appearance.
+///
+/// Arguments are asked only if they are typed, not if they are in a part whose condition does not
+/// hold. Arguments of conditions are asked before the condition is decided.
+fn ask_missing_args(
+    lines: &[Vec<LinePart>],
+    order: &[String],
+    defaults: &IndexMap<String, String>,
+    types: &IndexMap<String, ArgType>,
+    help: &IndexMap<String, ArgHelp>,
+    arg_values: &mut IndexMap<String, String>,
+    arglist_values: &mut IndexMap<String, Vec<String>>,
+) -> Result<(), Box<dyn Error>> {
+    while let Some((arg, list)) = next_arg(lines, order, arg_values, arglist_values) {
+        show_help(&arg, help);
+        match list {
+            false => ask_arg(&arg, defaults, types.get(&arg), arg_values)?,
+            true => ask_arglist(&arg, defaults, types.get(&arg), arglist_values)?,
+        }
     }
+    Ok(())
 }
 
-fn missing_line_arg<'a>(
-    line: &'a Vec<LinePart>,
+/// The next argument (`false`) or arglist (`true`) to ask, out of the ones known to be typed with
+/// the values asked so far.
+///
+/// Declared arguments come first, then the arguments of every line before its arglists.
+fn next_arg(
+    lines: &[Vec<LinePart>],
+    order: &[String],
     arg_values: &IndexMap<String, String>,
-) -> Option<&'a str> {
-    line.iter()
-        .find_map(|part| match part {
-            LinePart::Arg(arg) if !arg_values.contains_key(arg) => Some(arg.as_str()),
-            _ => None,
-        })
+    arglist_values: &IndexMap<String, Vec<String>>,
+) -> Option<(String, bool)> {
+    let mut next = None::<(usize, String, bool)>;
+    let mut consider = |arg: &str, list: bool| {
+        let rank = order
+            .iter()
+            .position(|declared| declared == arg)
+            .unwrap_or(order.len());
+        // the first one of the same rank is the first to appear
+        if next
+            .as_ref()
+            .is_none_or(|(best, ..)| rank < *best)
+        {
+            next = Some((rank, arg.to_string(), list));
+        }
+    };
+
+    let mut conditions = Conditions::default();
+    for line in lines {
+        let mut list_conditions = conditions.clone();
+
+        for part in line {
+            let arg = match part {
+                LinePart::Arg(arg, _) => arg,
+                LinePart::If(condition) => &condition.arg,
+                _ => {
+                    conditions.update(part, arg_values);
+                    continue;
+                },
+            };
+            if conditions.active() && !arg_values.contains_key(arg) {
+                consider(arg, false);
+            }
+            conditions.update_asked(part, arg_values);
+        }
+
+        for part in line {
+            list_conditions.update_asked(part, arg_values);
+            match part {
+                LinePart::ArgList(arg, _)
+                    if list_conditions.active() && !arglist_values.contains_key(arg) =>
+                {
+                    consider(arg, true)
+                },
+                _ => {},
+            }
+        }
+    }
+
+    next.map(|(_, arg, list)| (arg, list))
+}
+
+/// Print what the argument is for before asking it.
+fn show_help(arg: &str, help: &IndexMap<String, ArgHelp>) {
+    if let Some(help) = help.get(arg) {
+        println!("{arg}: {help}");
+    }
+}
+
+/// Names of the arguments (`false`) and arglists (`true`), the ones declared in the front matter
+/// (`order`) first and then in order of appearance.
+fn arg_names(lines: &[Vec<LinePart>], order: &[String]) -> Vec<(String, bool)> {
+    let mut names = Vec::new();
+    for part in lines
+        .iter()
+        .flatten()
+    {
+        let name = match part {
+            LinePart::Arg(arg, _) => (arg.clone(), false),
+            LinePart::If(condition) => (condition.arg.clone(), false),
+            LinePart::ArgList(arg, _) => (arg.clone(), true),
+            _ => continue,
+        };
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names.sort_by_key(|(name, _)| {
+        order
+            .iter()
+            .position(|declared| declared == name)
+            .unwrap_or(order.len())
+    });
+    names
+}
+
+/// Print the front matter metadata and the arguments of the snippet with their types, defaults
+/// and help, for `--list-args`.
+fn list_args(
+    metadata: &Metadata,
+    names: &[(String, bool)],
+    defaults: &IndexMap<String, String>,
+    types: &IndexMap<String, ArgType>,
+    help: &IndexMap<String, ArgHelp>,
+) {
+    let fields = [
+        ("name", &metadata.name),
+        ("description", &metadata.description),
+        ("author", &metadata.author),
+        ("snipped", &metadata.snipped),
+    ];
+    for (field, value) in fields {
+        if let Some(value) = value {
+            println!("{field}: {value}");
+        }
+    }
+
+    for (name, list) in names {
+        let arg_type = types.get(name);
+        println!(
+            "{}{name}{}{}",
+            match list {
+                true => "$[",
+                false => "$@",
+            },
+            type_hint(arg_type),
+            defaults
+                .get(name)
+                .map(|default| shown_default(default, arg_type))
+                .unwrap_or_default()
+        );
+        if let Some(help) = help.get(name) {
+            println!("    {help}");
+        }
+    }
+}
+
+/// Print the values the snippet is previewed with, for `--dry-run`.
+fn describe_args(
+    names: &[(String, bool)],
+    help: &IndexMap<String, ArgHelp>,
+    shown_values: &IndexMap<String, String>,
+    shown_list_values: &IndexMap<String, Vec<String>>,
+) {
+    for (name, list) in names {
+        let described = match list {
+            false => shown_values
+                .get(name)
+                .map(|value| format!("arg {name} = {value:?}")),
+            true => shown_list_values
+                .get(name)
+                .map(|values| format!("arglist {name} = {values:?}")),
+        };
+        // arguments in parts whose conditions do not hold are not asked
+        let Some(described) = described else {
+            continue;
+        };
+        match help.get(name) {
+            Some(help) => println!("{described} - {help}"),
+            None => println!("{described}"),
+        }
+    }
+}
+
+/// The declared type shown in a prompt, like ` [port]`.
+fn type_hint(arg_type: Option<&ArgType>) -> String {
+    arg_type
+        .map(|arg_type| format!(" [{arg_type}]"))
+        .unwrap_or_default()
+}
+
+/// Secret arguments are asked without echo, and their defaults are masked.
+fn ask_value(question: &str, arg_type: Option<&ArgType>) -> io::Result<Option<String>> {
+    match arg_type {
+        Some(ArgType::Secret) => secret::ask(question),
+        _ => ask_line(question),
+    }
 }
 
+fn shown_default(default: &str, arg_type: Option<&ArgType>) -> String {
+    match arg_type {
+        Some(ArgType::Secret) => format!(" (Default: {})", secret::MASK),
+        _ => format!(" (Default: `{default}`)"),
+    }
+}
+
+/// Invalid answers are asked again, unless there is no more input.
 fn ask_arg(
     arg: &str,
     defaults: &IndexMap<String, String>,
+    arg_type: Option<&ArgType>,
     arg_values: &mut IndexMap<String, String>,
-) -> io::Result<()> {
+) -> Result<(), Box<dyn Error>> {
     let default = match defaults.get(arg) {
         Some(default) => (
-            format!(" (Default: `{default}`)"),
+            shown_default(default, arg_type),
             Some(default),
         ),
         None => (String::new(), None),
     };
-    let answer = ask(&format!("{arg}{}", default.0))?;
-    let value = match answer {
-        s if s.is_empty() => default
-            .1
-            .cloned()
-            .unwrap_or_default(),
-        s => s,
-    };
-    arg_values.insert(arg.to_string(), value);
+    loop {
+        let answer = ask_value(
+            &format!(
+                "{arg}{}{}",
+                type_hint(arg_type),
+                default.0
+            ),
+            arg_type,
+        )?;
+        let ended = answer.is_none();
+        // the answer is moved, not copied, as it may be secret
+        let value = match answer {
+            Some(s) if !s.is_empty() => s,
+            _ => default
+                .1
+                .cloned()
+                .unwrap_or_default(),
+        };
+
+        match arg_type.map(|arg_type| arg_type.validate(&value)) {
+            Some(Err(e)) if !ended => println!("{e}, try again"),
+            Some(Err(e)) => return Err(format!("Invalid value for `{arg}`: {e}").into()),
+            _ => {
+                arg_values.insert(arg.to_string(), value);
+                return Ok(());
+            },
+        }
+    }
+}
+
+/// Ask values until an empty one, which is replaced by the default only if it is the first one.
+fn ask_arglist(
+    arg: &str,
+    defaults: &IndexMap<String, String>,
+    arg_type: Option<&ArgType>,
+    arglist_values: &mut IndexMap<String, Vec<String>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut values = Vec::new();
+    loop {
+        let default = match defaults.get(arg) {
+            Some(default) if values.is_empty() => shown_default(default, arg_type),
+            _ => " (Empty to finish)".to_string(),
+        };
+        let answer = ask_value(
+            &format!(
+                "{arg} #{}{}{default}",
+                values.len() + 1,
+                type_hint(arg_type)
+            ),
+            arg_type,
+        )?
+        .unwrap_or_default();
+        match answer {
+            s if s.is_empty() => {
+                if values.is_empty() {
+                    values.extend(defaults.get(arg).cloned());
+                }
+                break;
+            },
+            s => match arg_type.map(|arg_type| arg_type.validate(&s)) {
+                Some(Err(e)) => println!("{e}, try again"),
+                _ => values.push(s),
+            },
+        }
+    }
+    arglist_values.insert(arg.to_string(), values);
     Ok(())
 }
 
-fn type_text(enigo: &mut Enigo, text: &str) -> InputResult<()> {
+/// Lines are typed once for every value of their longest arglist (or once without arglists).
+fn line_repeats(line: &[LinePart], arglist_values: &IndexMap<String, Vec<String>>) -> usize {
+    line.iter()
+        .filter_map(|part| match part {
+            LinePart::ArgList(arg, _) => Some(arglist_values[arg].len()),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(1)
+}
+
+/// Shorter arglists are padded with empty values.
+fn arglist_value(values: &[String], i: usize) -> &str {
+    values
+        .get(i)
+        .map(String::as_str)
+        .unwrap_or_default()
+}
+
+fn type_text(
+    backend: &mut dyn Backend,
+    text: &str,
+    char_delay: Duration,
+    layout: Option<&Layout>,
+) -> Result<(), Box<dyn Error>> {
     for ch in text.chars() {
-        enigo.key(Key::Unicode(ch), Click)?;
-        sleep(Duration::from_millis(10));
+        match layout.and_then(|layout| layout.strokes(ch)) {
+            Some(strokes) =>
+                for stroke in strokes {
+                    let modifiers = [
+                        (stroke.altgr, PhysicalKey::ALTGR),
+                        (stroke.shift, PhysicalKey::SHIFT),
+                    ];
+                    for (_, modifier) in modifiers
+                        .iter()
+                        .filter(|(held, _)| *held)
+                    {
+                        backend.physical(*modifier, Press)?;
+                    }
+                    backend.physical(stroke.key, Click)?;
+                    for (_, modifier) in modifiers
+                        .iter()
+                        .rev()
+                        .filter(|(held, _)| *held)
+                    {
+                        backend.physical(*modifier, Release)?;
+                    }
+                },
+            None => backend.text(ch.encode_utf8(&mut [0; 4]))?,
+        }
+        backend.delay(char_delay)?;
     }
     Ok(())
 }
 
-fn key_combo(enigo: &mut Enigo, keys: &Vec<Key>) -> InputResult<()> {
+fn key_combo(backend: &mut dyn Backend, keys: &Vec<Key>) -> Result<(), Box<dyn Error>> {
     let mut to_release = Vec::<Key>::new();
 
     for key in keys {
@@ -136,9 +890,9 @@ fn key_combo(enigo: &mut Enigo, keys: &Vec<Key>) -> InputResult<()> {
             .rposition(|x| x == key)
             .map(|i| to_release.remove(i))
         {
-            Some(key) => enigo.key(key, Release)?,
+            Some(key) => backend.key(key, Release)?,
             None => {
-                enigo.key(*key, Press)?;
+                backend.key(*key, Press)?;
                 to_release.push(*key);
             },
         }
@@ -148,8 +902,342 @@ fn key_combo(enigo: &mut Enigo, keys: &Vec<Key>) -> InputResult<()> {
         .into_iter()
         .rev()
     {
-        enigo.key(key, Release)?;
+        backend.key(key, Release)?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::backend::Event;
+    use super::*;
+
+    #[test]
+    fn test_type_lines() {
+        let parse::Snippet { lines, .. } =
+            parse::snippet(Box::new("a$@x$\n$!Alt+Tab+Tab$".as_bytes())).unwrap();
+        let arg_values = IndexMap::from([(
+            "x".to_string(),
+            "b".to_string(),
+        )]);
+
+        let mut events = Vec::new();
+        type_lines(
+            &mut events,
+            &lines,
+            &arg_values,
+            &IndexMap::new(),
+            &IndexMap::new(),
+            &Typing::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            events,
+            [
+                Event::Delay(Duration::from_millis(100)),
+                Event::Text("a".to_string()),
+                Event::Delay(Duration::from_millis(10)),
+                Event::Text("b".to_string()),
+                Event::Delay(Duration::from_millis(10)),
+                Event::Key(Key::Return, Click),
+                Event::Delay(Duration::from_millis(100)),
+                Event::Key(Key::Alt, Press),
+                Event::Key(Key::Tab, Press),
+                Event::Key(Key::Tab, Release),
+                Event::Key(Key::Alt, Release),
+                Event::Key(Key::Return, Click),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_arglist_continuation() {
+        let parse::Snippet { lines, .. } = parse::snippet(Box::new(
+            "docker run -d \\ \n    -p $[Port::80:80$ \\ \n    nginx".as_bytes(),
+        ))
+        .unwrap();
+        let arglist_values = IndexMap::from([(
+            "Port".to_string(),
+            vec!["80:80".to_string(), "443:443".to_string()],
+        )]);
+
+        // every `-p` line ends with a submitted `\`, so the shell keeps reading the command
+        assert_eq!(
+            output::render(
+                &lines,
+                &IndexMap::new(),
+                &arglist_values,
+                &IndexMap::new(),
+            ),
+            "\
+            docker run -d \\\n    \
+            -p 80:80 \\\n    \
+            -p 443:443 \\\n    \
+            nginx\n"
+        );
+    }
+
+    #[test]
+    fn test_type_lines_settings() {
+        let parse::Snippet { lines, .. } = parse::snippet(Box::new(
+            "$~line=0$\na$~char=5$b$~char=default$c".as_bytes(),
+        ))
+        .unwrap();
+
+        let mut events = Vec::new();
+        type_lines(
+            &mut events,
+            &lines,
+            &IndexMap::new(),
+            &IndexMap::new(),
+            &IndexMap::new(),
+            &Typing::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            events,
+            [
+                Event::Delay(Duration::from_millis(0)),
+                Event::Text("a".to_string()),
+                Event::Delay(Duration::from_millis(10)),
+                Event::Text("b".to_string()),
+                Event::Delay(Duration::from_millis(5)),
+                Event::Text("c".to_string()),
+                Event::Delay(Duration::from_millis(10)),
+                Event::Key(Key::Return, Click),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_type_lines_line_end() {
+        let parse::Snippet { lines, .. } = parse::snippet(Box::new(
+            "$~eol=Control+j final-eol=none$\na\nb".as_bytes(),
+        ))
+        .unwrap();
+
+        let mut events = Vec::new();
+        type_lines(
+            &mut events,
+            &lines,
+            &IndexMap::new(),
+            &IndexMap::new(),
+            &IndexMap::new(),
+            &Typing::default(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            events,
+            [
+                Event::Delay(Duration::from_millis(100)),
+                Event::Text("a".to_string()),
+                Event::Delay(Duration::from_millis(10)),
+                Event::Key(Key::Control, Press),
+                Event::Key(Key::Unicode('j'), Press),
+                Event::Key(Key::Unicode('j'), Release),
+                Event::Key(Key::Control, Release),
+                Event::Delay(Duration::from_millis(100)),
+                Event::Text("b".to_string()),
+                Event::Delay(Duration::from_millis(10)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_type_lines_layout() {
+        let parse::Snippet { lines, .. } = parse::snippet(Box::new("Z@".as_bytes())).unwrap();
+        let layout = Layout::builtin("de").unwrap();
+
+        let mut events = Vec::new();
+        type_lines(
+            &mut events,
+            &lines,
+            &IndexMap::new(),
+            &IndexMap::new(),
+            &IndexMap::new(),
+            &Typing::default(),
+            Some(&layout),
+        )
+        .unwrap();
+
+        assert_eq!(
+            events,
+            [
+                Event::Delay(Duration::from_millis(100)),
+                Event::Physical(PhysicalKey::SHIFT, Press),
+                Event::Physical(PhysicalKey(21), Click),
+                Event::Physical(PhysicalKey::SHIFT, Release),
+                Event::Delay(Duration::from_millis(10)),
+                Event::Physical(PhysicalKey::ALTGR, Press),
+                Event::Physical(PhysicalKey(16), Click),
+                Event::Physical(PhysicalKey::ALTGR, Release),
+                Event::Delay(Duration::from_millis(10)),
+                Event::Key(Key::Return, Click),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_preview() {
+        let parse::Snippet { lines, .. } =
+            parse::snippet(Box::new("ab$'500$$!Alt+Tab$".as_bytes())).unwrap();
+
+        let mut output = Vec::new();
+        let mut preview = Preview::new(&mut output);
+        type_lines(
+            &mut preview,
+            &lines,
+            &IndexMap::new(),
+            &IndexMap::new(),
+            &IndexMap::new(),
+            &Typing::default(),
+            None,
+        )
+        .unwrap();
+        preview
+            .finish()
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\
+            delay 100ms\n\
+            type \"ab\" (20ms)\n\
+            delay 500ms\n\
+            press Alt\n\
+            press Tab\n\
+            release Tab\n\
+            release Alt\n\
+            click Return\n\
+            Total duration: 620ms\n"
+        );
+    }
+
+    #[test]
+    fn test_masked() {
+        let parse::Snippet { lines, types, .. } = parse::snippet(Box::new(
+            "login $@user$ $@password:secret$ $[token:secret$ $<pass show key$".as_bytes(),
+        ))
+        .unwrap();
+
+        let arg_values = IndexMap::from([
+            ("user".to_string(), "root".to_string()),
+            ("password".to_string(), "hunter2".to_string()),
+        ]);
+        let arglist_values = IndexMap::from([(
+            "token".to_string(),
+            vec!["a".to_string(), "b".to_string()],
+        )]);
+        let outputs = command::run_all(&lines, |_| Ok("s3cr3t".to_string())).unwrap();
+
+        let (shown_values, shown_list_values) = masked(&types, &arg_values, &arglist_values);
+        let shown_outputs = command::masked(&lines);
+        assert_eq!(
+            output::render(
+                &lines,
+                &shown_values,
+                &shown_list_values,
+                &shown_outputs,
+            ),
+            "\
+            login root ******** ******** ********\n\
+            login root ******** ******** ********\n"
+        );
+
+        let mut preview = Vec::new();
+        let mut backend = Preview::new(&mut preview);
+        type_lines(
+            &mut backend,
+            &lines,
+            &shown_values,
+            &shown_list_values,
+            &shown_outputs,
+            &Typing::default(),
+            None,
+        )
+        .unwrap();
+        backend
+            .finish()
+            .unwrap();
+        let preview = String::from_utf8(preview).unwrap();
+        assert!(preview.contains("********"));
+        assert!(!preview.contains("hunter2") && !preview.contains("s3cr3t"));
+
+        let mut typed = Vec::new();
+        type_lines(
+            &mut typed,
+            &lines,
+            &arg_values,
+            &arglist_values,
+            &outputs,
+            &Typing::default(),
+            None,
+        )
+        .unwrap();
+        let typed = typed
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Text(text) => Some(text),
+                _ => None,
+            })
+            .collect::<String>();
+        assert_eq!(
+            typed,
+            "login root hunter2 a s3cr3tlogin root hunter2 b s3cr3t"
+        );
+    }
+
+    #[test]
+    fn test_next_arg() {
+        let parse::Snippet {
+            declared,
+            lines,
+            ..
+        } = parse::snippet(Box::new(
+            "\
+            +++\n\
+            [args.User]\n\
+            [args.Host]\n\
+            +++\n\
+            ssh $^Proxy$-J $@User$@proxy $^$$@Host$ $[Option$"
+                .as_bytes(),
+        ))
+        .unwrap();
+        let order = declared
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+
+        // `User` is declared first, but only typed with a proxy
+        let mut arg_values = IndexMap::new();
+        let mut asked = Vec::new();
+        while let Some((arg, list)) = next_arg(&lines, &order, &arg_values, &IndexMap::new()) {
+            if list {
+                break;
+            }
+            arg_values.insert(arg.clone(), String::new());
+            asked.push(arg);
+        }
+        assert_eq!(asked, ["Host", "Proxy"]);
+
+        arg_values.insert("Proxy".to_string(), "yes".to_string());
+        assert_eq!(
+            next_arg(&lines, &order, &arg_values, &IndexMap::new()),
+            Some(("User".to_string(), false))
+        );
+        assert_eq!(
+            arg_names(&lines, &order)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>(),
+            ["User", "Host", "Proxy", "Option"]
+        );
+    }
+}
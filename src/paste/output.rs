@@ -0,0 +1,95 @@
+use std::error::Error;
+use std::io::stdout;
+use std::io::Write;
+
+use indexmap::IndexMap;
+use log::warn;
+
+use super::arglist_value;
+use super::filter;
+use super::line_repeats;
+use super::parse;
+use super::parse::LinePart;
+use crate::clipboard;
+use crate::create_file;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Output {
+    Print,
+    Clipboard,
+    File(String),
+}
+
+impl Output {
+    pub fn parse(target: &str) -> Self {
+        use Output::*;
+        match target.trim() {
+            "" | "-" => Print,
+            "!" => Clipboard,
+            path => File(path.to_string()),
+        }
+    }
+
+    pub fn write(&self, text: &str) -> Result<(), Box<dyn Error>> {
+        use Output::*;
+        match self {
+            Print => {
+                let mut stdout = stdout();
+                stdout.write_all(text.as_bytes())?;
+                stdout.flush()?;
+            },
+            Clipboard => clipboard::set_text(text)?,
+            File(path) =>
+                if let Some(mut file) = create_file(path)? {
+                    file.write_all(text.as_bytes())?;
+                },
+        }
+        Ok(())
+    }
+}
+
+/// Interpolate `arg_values` (and command `outputs`) into the lines, producing the text that would
+/// have been typed.
+///
+/// Delays are dropped, and key combos cannot be represented as text, so they are skipped.
+pub fn render(
+    lines: &[Vec<LinePart>],
+    arg_values: &IndexMap<String, String>,
+    arglist_values: &IndexMap<String, Vec<String>>,
+    outputs: &IndexMap<String, String>,
+) -> String {
+    let mut result = String::new();
+
+    for line in lines {
+        if parse::is_settings_only(line) {
+            continue;
+        }
+
+        for i in 0..line_repeats(line, arglist_values) {
+            for part in line {
+                match part {
+                    LinePart::Text(text) => result.push_str(text),
+                    LinePart::Delay(_)
+                    | LinePart::Settings(_)
+                    | LinePart::If(_)
+                    | LinePart::EndIf
+                    | LinePart::Env(_) => {},
+                    LinePart::Command(command) => result.push_str(&outputs[command]),
+                    LinePart::Arg(arg, filters) =>
+                        result.push_str(&filter::apply(filters, &arg_values[arg])),
+                    LinePart::ArgList(arg, filters) => result.push_str(&filter::apply(
+                        filters,
+                        arglist_value(&arglist_values[arg], i),
+                    )),
+                    LinePart::KeyCombo(keys) => warn!(
+                        "Key combo `{keys:?}` cannot be written to the output, skipping it."
+                    ),
+                }
+            }
+
+            result.push('\n');
+        }
+    }
+
+    result
+}
@@ -1,79 +1,755 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::io;
 use std::io::BufRead;
 use std::io::BufReader;
 use std::io::Read;
+use std::iter::Peekable;
+use std::rc::Rc;
 
 use indexmap::IndexMap;
-use log::warn;
+
+use super::arg_type::ArgType;
+use super::filter::Filter;
+use super::front_matter;
+use super::front_matter::Metadata;
+
+/// Part of the snippet source an error points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub text: String,
+    /// Line in the snippet, starting at 1.
+    pub line: usize,
+    /// Column (in characters) in the line, starting at 1.
+    pub column: usize,
+    /// The whole line the token starts on.
+    pub source: String,
+}
+
+/// Shows the source line with the token underlined by carets.
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let number = self
+            .line
+            .to_string();
+        let left = self
+            .source
+            .chars()
+            .count()
+            .saturating_sub(self.column - 1);
+        let width = self
+            .text
+            .chars()
+            .count()
+            .min(left)
+            .max(1);
+
+        writeln!(f, "{number} | {}", self.source)?;
+        write!(
+            f,
+            "{} | {}{}",
+            " ".repeat(number.len()),
+            " ".repeat(self.column - 1),
+            "^".repeat(width)
+        )
+    }
+}
+
+/// Write the message with the position and the caret diagnostic of the token.
+fn located(f: &mut Formatter<'_>, message: &str, token: &Token) -> fmt::Result {
+    write!(
+        f,
+        "{message} at line {}, column {}:\n{token}",
+        token.line, token.column
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Problem found in the snippet, warnings do not stop it from being parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub token: Token,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { located(f, &self.message, &self.token) }
+}
+
+#[derive(Debug, Clone)]
+pub enum Error {
+    InvalidDelay(Token),
+    UnknownKey(Token),
+    InvalidSettings(Token, String),
+    UnknownFilter(Token, String),
+    InvalidDefault(Token, String),
+    InvalidFrontMatter(Token, String),
+
+    IOError(Rc<io::Error>),
+}
+
+impl Error {
+    /// Message of the error and the token it points at, or the IO error that is not in the snippet.
+    fn message(&self) -> Result<(String, &Token), &io::Error> {
+        use Error::*;
+        Ok(match self {
+            InvalidDelay(token) => (
+                format!(
+                    "Delay `{}` is not a number of milliseconds, \
+                    you might've wanted to escape it with `$$'`",
+                    token.text
+                ),
+                token,
+            ),
+            UnknownKey(token) => (format!("Unknown key `{}`", token.text), token),
+            InvalidSettings(token, e) => (format!("Invalid settings `{}`: {e}", token.text), token),
+            UnknownFilter(token, filter) => (
+                format!(
+                    "Unknown filter `{filter}`, expected one of {}",
+                    Filter::NAMES.join(", ")
+                ),
+                token,
+            ),
+            InvalidDefault(token, e) => (
+                format!("Invalid default in `{}`: {e}", token.text),
+                token,
+            ),
+            InvalidFrontMatter(token, e) => (format!("Invalid front matter: {e}"), token),
+            IOError(e) => return Err(e),
+        })
+    }
+
+    /// The error as a diagnostic, if it points into the snippet.
+    pub fn diagnostic(&self) -> Option<Diagnostic> {
+        let (message, token) = self
+            .message()
+            .ok()?;
+        Some(Diagnostic {
+            severity: Severity::Error,
+            message,
+            token: token.clone(),
+        })
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.message() {
+            Ok((message, token)) => located(f, &message, token),
+            Err(e) => write!(f, "IO error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self { Error::IOError(err.into()) }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum LinePart {
     Text(String),
     Delay(u64),
-    Arg(String),
+    /// Argument name and the filters applied to its value.
+    Arg(String, Vec<Filter>),
+    ArgList(String, Vec<Filter>),
     KeyCombo(Vec<enigo::Key>),
+    Settings(Vec<Setting>),
+    /// Start of a part (or a block of lines) typed only if the condition holds, `$^...$`.
+    If(Condition),
+    /// End of the last started conditional part, `$^$`.
+    EndIf,
+    Env(EnvVar),
+    /// `$<command$`, replaced by the output of the command run on the host when pasting.
+    Command(String),
+}
+
+/// `$%NAME::default$`, replaced by the value of the environment variable when pasting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvVar {
+    pub name: String,
+    /// Used if the variable is not set, which is an error otherwise.
+    pub default: Option<String>,
+    pub filters: Vec<Filter>,
+}
+
+/// `$^arg$` holds if the argument is not empty, `$^arg=value$` if it equals the value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    pub arg: String,
+    pub value: Option<String>,
+}
+
+/// What an argument is for, from a `##? Name: description | example` line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArgHelp {
+    pub description: String,
+    pub example: Option<String>,
+}
+
+impl Display for ArgHelp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.description)?;
+        match &self.example {
+            Some(example) => write!(f, " (e.g. `{example}`)"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Split the `name|filter|filter:type` of an argument (without the default) into its parts.
+///
+/// `::` starts the default, so a single `:` is left to start the type, and filters come before
+/// the type, whose choices are separated by `|` as well. Either is only a separator if a known
+/// type or filter follows it, otherwise it is a part of the name, like in `$@Time (hh:mm)$`.
+pub fn split_arg(text: &str) -> (&str, Vec<&str>, Option<&str>) {
+    let (mut name, kind) = match text
+        .match_indices(':')
+        .find(|(at, _)| ArgType::parse(&text[at + 1..]).is_some())
+    {
+        Some((at, _)) => (&text[..at], Some(&text[at + 1..])),
+        None => (text, None),
+    };
+
+    let mut filters = Vec::new();
+    while let Some((rest, filter)) = name
+        .rsplit_once('|')
+        .filter(|(_, filter)| Filter::parse(filter).is_some())
+    {
+        filters.push(filter);
+        name = rest;
+    }
+    filters.reverse();
+    (name, filters, kind)
+}
+
+/// Start of a line describing an argument.
+pub const HELP_PREFIX: &str = "##?";
+
+/// Start of a line splicing in another snippet, `##include target`.
+pub const INCLUDE_PREFIX: &str = "##include";
+
+/// The rest of an include line, if the text starts with one (and not a comment like
+/// `##included ...`).
+pub fn include(text: &str) -> Option<&str> {
+    text.strip_prefix(INCLUDE_PREFIX)
+        .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+}
+
+/// Parse the ` Name: description | example` rest of a help line.
+fn help(text: &str) -> Option<(String, ArgHelp)> {
+    let (name, rest) = text.split_once(':')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    let (description, example) = match rest.rsplit_once('|') {
+        Some((description, example)) => (description, Some(example.trim().to_string())),
+        None => (rest, None),
+    };
+    Some((
+        name.to_string(),
+        ArgHelp {
+            description: description
+                .trim()
+                .to_string(),
+            example: example.filter(|example| !example.is_empty()),
+        },
+    ))
+}
+
+/// Changes how the rest of the snippet is typed, `None` restores the value set for the run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Setting {
+    CharDelay(Option<u64>),
+    LineDelay(Option<u64>),
+    LineEnd(Option<LineEnd>),
+    FinalLineEnd(Option<LineEnd>),
+}
+
+/// What is pressed after a typed line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineEnd {
+    Nothing,
+    Keys(Vec<enigo::Key>),
+}
+
+/// Parse `none` or a key combo like `Control+j`.
+pub fn line_end(text: &str) -> Result<LineEnd, Box<dyn std::error::Error>> {
+    if text == "none" {
+        return Ok(LineEnd::Nothing);
+    }
+
+    let mut keys = Vec::new();
+    for name in text.split('+') {
+        keys.push(key(name)?);
+    }
+    Ok(LineEnd::Keys(keys))
+}
+
+/// Common spellings of key names (compared case-insensitively) and the names they stand for.
+const KEY_ALIASES: &[(&str, &str)] = &[
+    ("alt", "Alt"),
+    ("backspace", "Backspace"),
+    ("control", "Control"),
+    ("ctrl", "Control"),
+    ("del", "Delete"),
+    ("delete", "Delete"),
+    ("down", "DownArrow"),
+    ("end", "End"),
+    ("enter", "Return"),
+    ("esc", "Escape"),
+    ("escape", "Escape"),
+    ("home", "Home"),
+    ("ins", "Insert"),
+    ("insert", "Insert"),
+    ("left", "LeftArrow"),
+    ("meta", "Meta"),
+    ("pgdn", "PageDown"),
+    ("pgup", "PageUp"),
+    ("return", "Return"),
+    ("right", "RightArrow"),
+    ("shift", "Shift"),
+    ("space", "Space"),
+    ("tab", "Tab"),
+    ("up", "UpArrow"),
+];
+
+/// The canonical spelling of a key name, like `Control` for `ctrl`.
+pub fn key_name(name: &str) -> &str {
+    KEY_ALIASES
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+        .map_or(name, |(_, canonical)| canonical)
+}
+
+/// Parse a key name like `Control` (or `Ctrl`), or a single character like `j`.
+pub fn key(name: &str) -> Result<enigo::Key, Box<dyn std::error::Error>> {
+    let mut chars = name.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next()) {
+        return Ok(enigo::Key::Unicode(ch));
+    }
+
+    Ok(serde_plain::from_str(key_name(name))?)
 }
 
 pub struct Snippet {
+    /// Name, description and the like, from the front matter.
+    pub metadata: Metadata,
+    /// Arguments declared in the front matter, in the order they are asked, with their table
+    /// headers.
+    pub declared: Vec<(String, Token)>,
     pub lines: Vec<Vec<LinePart>>,
+    /// Source line each of `lines` starts on.
+    pub line_numbers: Vec<usize>,
     pub defaults: IndexMap<String, String>,
+    /// Declared types of the arguments, `$@name:type$`.
+    pub types: IndexMap<String, ArgType>,
+    /// Descriptions of the arguments, in the order they are given.
+    pub help: IndexMap<String, ArgHelp>,
+    /// `##include` targets, with the index of `lines` their lines go to.
+    pub includes: Vec<(usize, String)>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// What the lines parsed so far add up to.
+#[derive(Default)]
+struct Parsed {
+    metadata: Metadata,
+    /// Arguments declared in the front matter, with their table headers.
+    declared: Vec<(String, Token)>,
+    /// Arguments with a default from the front matter, which an inline default overrides without
+    /// a warning.
+    declared_defaults: HashSet<String>,
+    lines: Vec<Vec<LinePart>>,
+    defaults: IndexMap<String, String>,
+    types: IndexMap<String, ArgType>,
+    help: IndexMap<String, ArgHelp>,
+    includes: Vec<(usize, String)>,
+    diagnostics: Vec<Diagnostic>,
+    /// Started conditions that are not ended yet.
+    conditions: Vec<Token>,
+}
+
+/// Warnings for the arguments declared in the front matter but never used in the lines, which
+/// should have their `##include`s spliced in already.
+pub fn unused_declarations(
+    lines: &[Vec<LinePart>],
+    declared: &[(String, Token)],
+) -> Vec<Diagnostic> {
+    declared
+        .iter()
+        .filter(|(name, _)| {
+            !lines
+                .iter()
+                .flatten()
+                .any(|part| match part {
+                    LinePart::Arg(arg, _) | LinePart::ArgList(arg, _) => arg == name,
+                    LinePart::If(condition) => condition.arg == *name,
+                    _ => false,
+                })
+        })
+        .map(|(name, token)| Diagnostic {
+            severity: Severity::Warning,
+            message: format!("Argument `{name}` is declared in the front matter, but never used"),
+            token: token.clone(),
+        })
+        .collect()
+}
+
+/// Processed comments (`##`) are kept in the lines, so that their arguments are still asked.
+pub fn is_comment(line: &[LinePart]) -> bool {
+    match line.first() {
+        Some(LinePart::Text(first)) => first.starts_with("##"),
+        _ => false,
+    }
+}
+
+/// Lines with nothing but settings (`$~`) are not typed (and not submitted).
+pub fn is_settings_only(line: &[LinePart]) -> bool {
+    line.iter()
+        .any(|part| matches!(part, LinePart::Settings(_)))
+        && line
+            .iter()
+            .all(|part| match part {
+                LinePart::Settings(_) => true,
+                LinePart::Text(text) => text.is_empty(),
+                _ => false,
+            })
 }
 
-pub fn snippet_line(
+/// Parse `char=50 line=default eol=none`-like contents of `$~...$`.
+pub fn settings(text: &str) -> Result<Vec<Setting>, Box<dyn std::error::Error>> {
+    let mut result = Vec::new();
+    for setting in text.split([' ', ',']) {
+        if setting.is_empty() {
+            continue;
+        }
+
+        let (key, value) = setting
+            .split_once('=')
+            .ok_or_else(|| format!("Setting `{setting}` has no `=value`"))?;
+        let value = match value {
+            "default" => None,
+            value => Some(value.to_string()),
+        };
+
+        result.push(match key {
+            "char" => Setting::CharDelay(value.map(|v| v.parse()).transpose()?),
+            "line" => Setting::LineDelay(value.map(|v| v.parse()).transpose()?),
+            "eol" => Setting::LineEnd(value.map(|v| line_end(&v)).transpose()?),
+            "final-eol" => Setting::FinalLineEnd(value.map(|v| line_end(&v)).transpose()?),
+            _ => return Err(format!("Unknown setting `{key}`").into()),
+        });
+    }
+    Ok(result)
+}
+
+fn next_is(chars: &mut Peekable<impl Iterator<Item = (usize, char)>>, ch: char) -> bool {
+    matches!(chars.peek(), Some((_, next)) if *next == ch)
+}
+
+/// Parse the front matter (the lines between the delimiters, with their numbers), before the
+/// lines after it, so that arguments declared in it can be redeclared in the lines.
+fn front_matter_at(lines: &[(usize, String)], parsed: &mut Parsed) -> Result<(), Error> {
+    let token = |number: usize| {
+        let source = lines
+            .iter()
+            .find(|(line, _)| *line == number)
+            .map_or(String::new(), |(_, source)| source.clone());
+        let text = source.trim_start();
+        Token {
+            text: text
+                .trim_end()
+                .to_string(),
+            line: number,
+            column: source[..source.len() - text.len()]
+                .chars()
+                .count()
+                + 1,
+            source: source.clone(),
+        }
+    };
+
+    let front_matter = front_matter::parse(lines)
+        .map_err(|(number, e)| Error::InvalidFrontMatter(token(number), e))?;
+
+    for (name, declaration) in front_matter.args {
+        if let Some(default) = declaration.default {
+            parsed
+                .defaults
+                .insert(name.clone(), default);
+            parsed
+                .declared_defaults
+                .insert(name.clone());
+        }
+        if let Some(arg_type) = declaration.arg_type {
+            parsed
+                .types
+                .insert(name.clone(), arg_type);
+        }
+        if let Some(help) = declaration.help {
+            parsed
+                .help
+                .insert(name.clone(), help);
+        }
+        parsed
+            .declared
+            .push((name, token(declaration.line)));
+    }
+    parsed.metadata = front_matter.metadata;
+    Ok(())
+}
+
+/// Parse a (possibly joined) line of a snippet.
+///
+/// `origins` has the source line and column of every character, `sources` the source lines.
+fn snippet_line_at(
     curr_line: &str,
-    lines: &mut Vec<Vec<LinePart>>,
-    defaults: &mut IndexMap<String, String>,
-) -> Result<(), Box<dyn std::error::Error>> {
+    origins: &[(usize, usize)],
+    sources: &IndexMap<usize, String>,
+    parsed: &mut Parsed,
+) -> Result<(), Error> {
     enum ProcessingPart {
         Text(String),
         Delay(String),
         Arg {
             value: String,
             default: Option<String>,
+            list: bool,
+            /// Type after the name, `name:type`.
+            kind: Option<String>,
+            /// Filters after the name, `name|filter`.
+            filters: Vec<String>,
         },
         KeyCombo(Vec<String>),
+        Settings(String),
+        Condition(String),
+        Env {
+            value: String,
+            default: Option<String>,
+        },
+        Command(String),
     }
     use ProcessingPart::*;
 
-    let mut result = vec![Text(String::new())];
+    let len = curr_line
+        .chars()
+        .count();
+    let token = |start: usize, end: usize| {
+        let (line, column) = origins
+            .get(start)
+            .copied()
+            .unwrap_or((1, start + 1));
+        Token {
+            text: curr_line
+                .chars()
+                .skip(start)
+                .take(end - start)
+                .collect(),
+            line,
+            column,
+            source: sources
+                .get(&line)
+                .cloned()
+                .unwrap_or_default(),
+        }
+    };
+    let mut warn = |message: String, start: usize, end: usize| {
+        parsed
+            .diagnostics
+            .push(Diagnostic {
+                severity: Severity::Warning,
+                message,
+                token: token(start, end),
+            })
+    };
+
+    // help lines are kept as processed comments, nothing else in them is parsed
+    if let Some(text) = curr_line.strip_prefix(HELP_PREFIX) {
+        match help(text) {
+            Some((name, help)) => {
+                if parsed
+                    .help
+                    .insert(name.clone(), help)
+                    .is_some()
+                {
+                    warn(
+                        format!("Duplicate help for argument `{name}`, the last one is used"),
+                        0,
+                        len,
+                    );
+                }
+            },
+            None => warn(
+                format!(
+                    "Help line should look like `{HELP_PREFIX} Name: description | example`, \
+                    it is ignored"
+                ),
+                0,
+                len,
+            ),
+        }
+        parsed
+            .lines
+            .push(vec![LinePart::Text(curr_line.to_string())]);
+        return Ok(());
+    }
+
+    // include lines are kept as processed comments too, the included lines go right after them
+    if let Some(target) = include(curr_line) {
+        match target.trim() {
+            "" => warn(
+                format!("Include line should look like `{INCLUDE_PREFIX} target`, it is ignored"),
+                0,
+                len,
+            ),
+            target => parsed
+                .includes
+                .push((parsed.lines.len() + 1, target.to_string())),
+        }
+        parsed
+            .lines
+            .push(vec![LinePart::Text(curr_line.to_string())]);
+        return Ok(());
+    }
+
+    // parts are kept with the index of the character they start at
+    let mut result = vec![(0, Text(String::new()))];
 
     let mut chars = curr_line
         .chars()
+        .enumerate()
         .peekable();
+    // index right after the last `$$` escape
+    let mut escape_end = None;
     // apparently I cannot use `for ch in chars`,
     // even though the result should be the same. semantics.
-    while let Some(ch) = chars.next() {
-        match result
+    while let Some((i, ch)) = chars.next() {
+        let (part_start, _) = result
+            .last()
+            .unwrap();
+        let part_start = *part_start;
+
+        match &mut result
             .last_mut()
             .unwrap()
+            .1
         {
             //
             // Text and staring others
             //
 
+            // escape `$$` as `$`
+            Text(text) if ch == '$' && next_is(&mut chars, '$') => {
+                chars.next();
+                text.push('$');
+                // `$$` used to be typed as-is, like `kill $$`, so one that escapes nothing is
+                // reported, unless it is a part of a longer `$$$$`
+                let escapes = matches!(
+                    chars.peek(),
+                    Some((_, '\'' | '@' | '[' | '!' | '~' | '^' | '%' | '<' | '$'))
+                );
+                if !escapes && escape_end != Some(i) {
+                    warn(
+                        "`$$` is typed as a single `$`, write `$$$$` to type `$$`".to_string(),
+                        i,
+                        i + 2,
+                    );
+                }
+                escape_end = Some(i + 2);
+            },
+
             // start delay with `$'`
-            Text(_) if ch == '$' && chars.peek() == Some(&'\'') => {
+            Text(_) if ch == '$' && next_is(&mut chars, '\'') => {
                 chars.next();
-                result.push(Delay(String::new()));
+                result.push((i, Delay(String::new())));
             },
 
             // start arg with `$@`
-            Text(_) if ch == '$' && chars.peek() == Some(&'@') => {
+            Text(_) if ch == '$' && next_is(&mut chars, '@') => {
                 chars.next();
-                result.push(Arg {
-                    value: String::new(),
-                    default: None,
-                });
+                result.push((
+                    i,
+                    Arg {
+                        value: String::new(),
+                        default: None,
+                        list: false,
+                        kind: None,
+                        filters: Vec::new(),
+                    },
+                ));
             },
 
             // start arglist with `$[`
-            Text(_) if ch == '$' && chars.peek() == Some(&'[') => {
-                todo!("Arglist is not supported yet");
+            Text(_) if ch == '$' && next_is(&mut chars, '[') => {
+                chars.next();
+                result.push((
+                    i,
+                    Arg {
+                        value: String::new(),
+                        default: None,
+                        list: true,
+                        kind: None,
+                        filters: Vec::new(),
+                    },
+                ));
             },
 
             // start key combo with `$!`
-            Text(_) if ch == '$' && chars.peek() == Some(&'!') => {
+            Text(_) if ch == '$' && next_is(&mut chars, '!') => {
+                chars.next();
+                result.push((i, KeyCombo(vec![String::new()])));
+            },
+
+            // start settings with `$~`
+            Text(_) if ch == '$' && next_is(&mut chars, '~') => {
+                chars.next();
+                result.push((i, Settings(String::new())));
+            },
+
+            // start condition with `$^`
+            Text(_) if ch == '$' && next_is(&mut chars, '^') => {
+                chars.next();
+                result.push((i, Condition(String::new())));
+            },
+
+            // start environment variable with `$%`
+            Text(_) if ch == '$' && next_is(&mut chars, '%') => {
+                chars.next();
+                result.push((
+                    i,
+                    Env {
+                        value: String::new(),
+                        default: None,
+                    },
+                ));
+            },
+
+            // start host command with `$<`
+            Text(_) if ch == '$' && next_is(&mut chars, '<') => {
                 chars.next();
-                result.push(KeyCombo(vec![String::new()]));
+                result.push((i, Command(String::new())));
             },
 
             // just push char at the end of text
@@ -83,50 +759,80 @@ pub fn snippet_line(
             // Delay
             //
             Delay(_) if ch == '$' => {
-                result.push(Text(String::new()));
+                result.push((i + 1, Text(String::new())));
             },
 
             // just push char at the end of delay string
             Delay(delay) => delay.push(ch),
 
             //
-            // Arg
+            // Arg and Arglist
             //
 
-            // handle `$@$` as `$@` (default is ignored)
-            Arg { value, .. } if ch == '$' && value.is_empty() => {
-                result.truncate(result.len() - 1);
-                match result
+            // handle `$@$` as `$@` and `$[$` as `$[` (default is ignored)
+            Arg { value, list, .. } if ch == '$' && value.is_empty() => {
+                let escaped = match list {
+                    true => "$[",
+                    false => "$@",
+                };
+
+                let (start, _) = result
+                    .pop()
+                    .unwrap();
+                match &mut result
                     .last_mut()
                     .unwrap()
+                    .1
                 {
-                    Text(text) => text.push_str("$@"),
-                    _ => result.push(Text("$@".to_string())),
+                    Text(text) => text.push_str(escaped),
+                    _ => result.push((start, Text(escaped.to_string()))),
                 }
             },
 
             // handle ending of arg
-            Arg { value, default } if ch == '$' => {
+            Arg {
+                value,
+                default,
+                kind,
+                filters,
+                ..
+            } if ch == '$' => {
+                let (name, names, arg_type) = split_arg(value);
+                *filters = names
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect();
+                *kind = arg_type.map(str::to_string);
+                *value = name.to_string();
+
                 if let Some(default) = default {
-                    if let Some(previous) = defaults.insert(
-                        value.to_string(),
-                        default.to_string(),
-                    ) {
-                        warn!(
-                            "Duplicate default value for argument `{value}`. \
-                            Previous value `{previous}` will be ignored."
+                    let previous = parsed
+                        .defaults
+                        .insert(value.to_string(), default.to_string());
+                    // only the first inline default overrides the declared one
+                    let declared = parsed
+                        .declared_defaults
+                        .remove(value.as_str());
+                    if let (Some(previous), false) = (previous, declared) {
+                        warn(
+                            format!(
+                                "Duplicate default value for argument `{value}`. \
+                                Previous value `{previous}` will be ignored."
+                            ),
+                            part_start,
+                            i + 1,
                         );
                     }
                 }
 
-                result.push(Text(String::new()));
+                result.push((i + 1, Text(String::new())));
             },
 
             // handle default start
             Arg {
                 default: default @ None,
                 ..
-            } if ch == ':' && chars.peek() == Some(&':') => {
+            } if ch == ':' && next_is(&mut chars, ':') => {
                 chars.next();
                 *default = Some(String::new());
             },
@@ -135,18 +841,13 @@ pub fn snippet_line(
             Arg {
                 value,
                 default: None,
+                ..
             } => value.push(ch),
             Arg {
                 default: Some(default),
                 ..
             } => default.push(ch),
 
-            //
-            // Arglist
-            //
-
-            // TODO
-
             //
             // KeyCombo
             //
@@ -163,9 +864,24 @@ pub fn snippet_line(
                 combo.push(String::new());
             },
 
+            // handle `$!$` as `$!`
+            KeyCombo(combo) if ch == '$' && combo == &[""] => {
+                let (start, _) = result
+                    .pop()
+                    .unwrap();
+                match &mut result
+                    .last_mut()
+                    .unwrap()
+                    .1
+                {
+                    Text(text) => text.push_str("$!"),
+                    _ => result.push((start, Text("$!".to_string()))),
+                }
+            },
+
             // handle key combo end
             KeyCombo(..) if ch == '$' => {
-                result.push(Text(String::new()));
+                result.push((i + 1, Text(String::new())));
             },
 
             // just push char at the end of key combo
@@ -173,30 +889,144 @@ pub fn snippet_line(
                 .last_mut()
                 .unwrap()
                 .push(ch),
+
+            //
+            // Settings
+            //
+
+            // handle `$~$` as `$~`
+            Settings(settings) if ch == '$' && settings.is_empty() => {
+                let (start, _) = result
+                    .pop()
+                    .unwrap();
+                match &mut result
+                    .last_mut()
+                    .unwrap()
+                    .1
+                {
+                    Text(text) => text.push_str("$~"),
+                    _ => result.push((start, Text("$~".to_string()))),
+                }
+            },
+
+            // handle settings end
+            Settings(_) if ch == '$' => {
+                result.push((i + 1, Text(String::new())));
+            },
+
+            // just push char at the end of settings
+            Settings(settings) => settings.push(ch),
+
+            //
+            // Condition
+            //
+
+            // handle condition end (`$^$` ends the conditional part instead)
+            Condition(_) if ch == '$' => {
+                result.push((i + 1, Text(String::new())));
+            },
+
+            // just push char at the end of condition
+            Condition(condition) => condition.push(ch),
+
+            //
+            // Env
+            //
+
+            // handle `$%$` as `$%`
+            Env { value, .. } if ch == '$' && value.is_empty() => {
+                let (start, _) = result
+                    .pop()
+                    .unwrap();
+                match &mut result
+                    .last_mut()
+                    .unwrap()
+                    .1
+                {
+                    Text(text) => text.push_str("$%"),
+                    _ => result.push((start, Text("$%".to_string()))),
+                }
+            },
+
+            // handle environment variable end
+            Env { .. } if ch == '$' => {
+                result.push((i + 1, Text(String::new())));
+            },
+
+            // handle default start
+            Env {
+                default: default @ None,
+                ..
+            } if ch == ':' && next_is(&mut chars, ':') => {
+                chars.next();
+                *default = Some(String::new());
+            },
+
+            // just push char at the end of environment variable
+            Env {
+                value,
+                default: None,
+            } => value.push(ch),
+            Env {
+                default: Some(default),
+                ..
+            } => default.push(ch),
+
+            //
+            // Command
+            //
+
+            // handle `$<$` as `$<`
+            Command(command) if ch == '$' && command.is_empty() => {
+                let (start, _) = result
+                    .pop()
+                    .unwrap();
+                match &mut result
+                    .last_mut()
+                    .unwrap()
+                    .1
+                {
+                    Text(text) => text.push_str("$<"),
+                    _ => result.push((start, Text("$<".to_string()))),
+                }
+            },
+
+            // handle command end
+            Command(_) if ch == '$' => {
+                result.push((i + 1, Text(String::new())));
+            },
+
+            // just push char at the end of command
+            Command(command) => command.push(ch),
         }
     }
 
-    if let Arg { value, .. } = result
+    let (last_start, last) = result
         .last()
-        .unwrap()
-    {
+        .unwrap();
+    if let Arg { value, list, .. } = last {
+        let (start, kind) = match list {
+            true => ("$[", "arglist"),
+            false => ("$@", "arg"),
+        };
+
         let escape_result = match value.is_empty() {
-            true => r#"text "$@""#.to_string(),
-            false => "arg".to_string(),
+            true => format!(r#"text "{start}""#),
+            false => kind.to_string(),
         };
 
-        warn!(
-            // TODO make log crate capture these warnings (so they can be tested for)
-            "Argument `$@{value}` is incomplete, \
-            you might've wanted to complete it or escape it with `$@$`. \
-            Autocompleting as `$@{value}$` ({escape_result})."
+        warn(
+            format!(
+                "Argument `{start}{value}` is incomplete, \
+                you might've wanted to complete it or escape it with `{start}$`. \
+                Autocompleting as `{start}{value}$` ({escape_result})."
+            ),
+            *last_start,
+            len,
         );
     }
 
-    if let KeyCombo(combo) = result
-        .last()
-        .unwrap()
-    {
+    if let KeyCombo(combo) = last {
         let escape_result = match combo.is_empty() {
             true => r#"text "$!""#.to_string(),
             false => "key combo".to_string(),
@@ -208,100 +1038,472 @@ pub fn snippet_line(
             .collect::<Vec<_>>()
             .join("+");
 
-        warn!(
-            "Key combo `$!{combo}` is incomplete, \
-            you might've wanted to complete it or escape it with `$!$`. \
-            Autocompleting as `$!{combo}$` ({escape_result})."
+        warn(
+            format!(
+                "Key combo `$!{combo}` is incomplete, \
+                you might've wanted to complete it or escape it with `$!$`. \
+                Autocompleting as `$!{combo}$` ({escape_result})."
+            ),
+            *last_start,
+            len,
+        );
+    }
+
+    if let Settings(settings) = last {
+        warn(
+            format!(
+                "Settings `$~{settings}` are incomplete, \
+                you might've wanted to complete them or escape them with `$~$`. \
+                Autocompleting as `$~{settings}$`."
+            ),
+            *last_start,
+            len,
+        );
+    }
+
+    if let Env { value, .. } = last {
+        warn(
+            format!(
+                "Environment variable `$%{value}` is incomplete, \
+                you might've wanted to complete it or escape it with `$%$`. \
+                Autocompleting as `$%{value}$`."
+            ),
+            *last_start,
+            len,
+        );
+    }
+
+    if let Command(command) = last {
+        warn(
+            format!(
+                "Command `$<{command}` is incomplete, \
+                you might've wanted to complete it or escape it with `$<$`. \
+                Autocompleting as `$<{command}$`."
+            ),
+            *last_start,
+            len,
+        );
+    }
+
+    if let Condition(condition) = last {
+        warn(
+            format!(
+                "Condition `$^{condition}` is incomplete, \
+                you might've wanted to complete it or escape it with `$$^`. \
+                Autocompleting as `$^{condition}$`."
+            ),
+            *last_start,
+            len,
         );
     }
 
     // trim trailing empty `Text`
-    if let Text(text) = result
+    if let Text(text) = &result
         .last()
         .unwrap()
+        .1
     {
         if text.is_empty() {
             result.pop();
         }
     }
 
+    // each part ends where the next one starts
+    let ends = result
+        .iter()
+        .skip(1)
+        .map(|(start, _)| *start)
+        .chain([len])
+        .collect::<Vec<_>>();
+
+    let parse_filters = |names: Vec<String>, start: usize, end: usize| {
+        names
+            .into_iter()
+            .map(|name| {
+                Filter::parse(&name).ok_or_else(|| Error::UnknownFilter(token(start, end), name))
+            })
+            .collect::<Result<Vec<_>, _>>()
+    };
+
     // save parts as a new line
     let mut line = Vec::new();
-    for part in result {
+    for ((start, part), end) in result
+        .into_iter()
+        .zip(ends)
+    {
         line.push(match part {
             Text(text) => LinePart::Text(text),
-            Delay(delay) => LinePart::Delay(delay.parse()?),
-            Arg { value, .. } => LinePart::Arg(value),
-            KeyCombo(combo) => {
-                let mut res = Vec::new();
-                for k in combo {
-                    res.push(serde_plain::from_str(&k)?);
+            Delay(delay) => LinePart::Delay(
+                delay
+                    .parse()
+                    .map_err(|_| Error::InvalidDelay(token(start, end)))?,
+            ),
+            Arg {
+                value,
+                default,
+                list,
+                kind,
+                filters,
+            } => {
+                let filters = parse_filters(filters, start, end)?;
+
+                // only known types are split from the name
+                let declared = kind.is_some();
+                if let Some(arg_type) = kind
+                    .as_deref()
+                    .and_then(ArgType::parse)
+                {
+                    if let Some(previous) = parsed
+                        .types
+                        .insert(value.clone(), arg_type.clone())
+                        .filter(|previous| *previous != arg_type)
+                    {
+                        warn(
+                            format!(
+                                "Argument `{value}` was declared as `{previous}` before, \
+                                it is `{arg_type}` from now on"
+                            ),
+                            start,
+                            end,
+                        );
+                    }
                 }
-                LinePart::KeyCombo(res)
-            },
+
+                // the default is checked wherever the type or the default is given
+                if let (Some(arg_type), Some(default)) = (
+                    parsed
+                        .types
+                        .get(&value),
+                    parsed
+                        .defaults
+                        .get(&value)
+                        .filter(|_| declared || default.is_some()),
+                ) {
+                    arg_type
+                        .validate(default)
+                        .map_err(|e| Error::InvalidDefault(token(start, end), e))?;
+                }
+
+                match list {
+                    true => LinePart::ArgList(value, filters),
+                    false => LinePart::Arg(value, filters),
+                }
+            },
+            KeyCombo(combo) => {
+                let combo_text = token(start, end).text;
+                // skip `$!`
+                let mut from = 2;
+
+                let mut res = Vec::new();
+                // separator at the end leaves an empty key
+                for k in combo
+                    .iter()
+                    .filter(|k| !k.is_empty())
+                {
+                    let at = from
+                        + combo_text[from..]
+                            .find(k.as_str())
+                            .unwrap_or(0);
+                    from = at + k.len();
+
+                    res.push(key(k).map_err(|_| {
+                        let skip = combo_text[..at]
+                            .chars()
+                            .count();
+                        Error::UnknownKey(token(
+                            start + skip,
+                            start + skip + k.chars().count(),
+                        ))
+                    })?);
+                }
+                LinePart::KeyCombo(res)
+            },
+            Settings(text) => LinePart::Settings(
+                settings(&text)
+                    .map_err(|e| Error::InvalidSettings(token(start, end), e.to_string()))?,
+            ),
+            Env { value, default } => {
+                let mut names = value
+                    .split('|')
+                    .map(str::to_string);
+                LinePart::Env(EnvVar {
+                    name: names
+                        .next()
+                        .unwrap_or_default(),
+                    default,
+                    filters: parse_filters(names.collect(), start, end)?,
+                })
+            },
+            Command(command) => LinePart::Command(command),
+            Condition(condition) if condition.is_empty() => {
+                if parsed
+                    .conditions
+                    .pop()
+                    .is_none()
+                {
+                    warn(
+                        "Condition end `$^$` does not end any condition, \
+                        you might've wanted to escape it with `$$^`."
+                            .to_string(),
+                        start,
+                        end,
+                    );
+                }
+                LinePart::EndIf
+            },
+            Condition(condition) => {
+                parsed
+                    .conditions
+                    .push(token(start, end));
+                let (arg, value) = match condition.split_once('=') {
+                    Some((arg, value)) => (arg, Some(value.to_string())),
+                    None => (condition.as_str(), None),
+                };
+                LinePart::If(self::Condition {
+                    arg: arg.to_string(),
+                    value,
+                })
+            },
         });
     }
-    lines.push(line);
+    parsed
+        .lines
+        .push(line);
     Ok(())
 }
 
-pub fn snippet(snippet: Box<dyn Read>) -> Result<Snippet, Box<dyn std::error::Error>> {
-    let mut lines = Vec::new();
+pub fn snippet(snippet: Box<dyn Read>) -> Result<Snippet, Error> {
+    let (snippet, errors) = snippet_with_errors(snippet)?;
+    match errors
+        .into_iter()
+        .next()
+    {
+        Some(error) => Err(error),
+        None => Ok(snippet),
+    }
+}
+
+/// Parse the whole snippet, collecting the errors of all lines instead of stopping at the first.
+///
+/// Lines with errors are left out of the snippet.
+pub fn snippet_with_errors(snippet: Box<dyn Read>) -> io::Result<(Snippet, Vec<Error>)> {
+    let mut parsed = Parsed::default();
+    let mut line_numbers = Vec::new();
+    let mut errors = Vec::new();
     let mut curr_line = String::new();
     let mut appending = false;
-    let mut defaults = IndexMap::new();
 
-    for input in BufReader::new(snippet).lines() {
-        let input = input?;
-        let mut input = input.as_str();
+    // where each character of `curr_line` comes from, for errors
+    let mut origins = Vec::new();
+    let mut sources = IndexMap::new();
+    // lines of the front matter while it is read
+    let mut front = None::<Vec<(usize, String)>>;
+
+    for (number, source) in BufReader::new(snippet)
+        .lines()
+        .enumerate()
+    {
+        let source = source?;
+        let number = number + 1;
+
+        // front matter can only start on the first line
+        if number == 1 && source.trim_end() == front_matter::DELIMITER {
+            front = Some(Vec::new());
+            continue;
+        }
+        if let Some(lines) = &mut front {
+            match source.trim_end() == front_matter::DELIMITER {
+                true => {
+                    if let Err(error) = front_matter_at(lines, &mut parsed) {
+                        errors.push(error);
+                    }
+                    front = None;
+                },
+                false => lines.push((number, source)),
+            }
+            continue;
+        }
+        let mut input = source.as_str();
+        let mut column = 1;
 
         // trim leading whitespace if appending line to previous one
         if appending {
             appending = false;
             input = input.trim_start();
+            column += source[..source.len() - input.len()]
+                .chars()
+                .count();
         }
 
         // append lines
         // no `input.trim_end().ends_with('\\')`, `\ ` will not append
         // `\\` (double backslash) at end will push first backslash and eat 2nd as an append
-        if input.ends_with('\\') {
-            curr_line.push_str(&input[..input.len() - 1]);
+        if let Some(stripped) = input.strip_suffix('\\') {
+            input = stripped;
             appending = true;
-            continue;
+        } else if let Some(stripped) = input
+            .trim_end()
+            .strip_suffix('\\')
+        {
+            // `\ ` is typed without the whitespace, so that the submitted line ends with `\`
+            // and the shell continues the command on the next one
+            input = &input[..stripped.len() + 1];
         }
-
-        // else append to `curr_line`
         curr_line.push_str(input);
+        origins.extend(
+            (column..)
+                .take(input.chars().count())
+                .map(|column| (number, column)),
+        );
+        sources.insert(number, source.clone());
+        if appending {
+            continue;
+        }
 
         // and `curr_line` is done, parse parts from it
-        snippet_line(
-            &curr_line,
-            &mut lines,
-            &mut defaults,
-        )?;
+        match snippet_line_at(&curr_line, &origins, &sources, &mut parsed) {
+            Ok(()) => line_numbers.push(*sources
+                .first()
+                .unwrap()
+                .0),
+            Err(error) => errors.push(error),
+        }
         curr_line.clear();
+        origins.clear();
+        sources.clear();
+    }
+
+    if front.is_some() {
+        errors.push(Error::InvalidFrontMatter(
+            Token {
+                text: front_matter::DELIMITER.to_string(),
+                line: 1,
+                column: 1,
+                source: front_matter::DELIMITER.to_string(),
+            },
+            format!("it is never ended with `{}`", front_matter::DELIMITER),
+        ));
     }
 
     if appending {
-        warn!(
-            "Last line ended with an appending backslash `\\`, \
-            assuming there is an empty line after it to append nothing. \
-            To use a backslash, append it with a whitespace (`\\ `)."
-        );
-        snippet_line(
-            &curr_line,
-            &mut lines,
-            &mut defaults,
-        )?;
+        let (&line, source) = sources
+            .last()
+            .unwrap();
+        parsed
+            .diagnostics
+            .push(Diagnostic {
+                severity: Severity::Warning,
+                message: "Last line ended with an appending backslash `\\`, \
+                    assuming there is an empty line after it to append nothing. \
+                    To use a backslash, append it with a whitespace (`\\ `)."
+                    .to_string(),
+                token: Token {
+                    text: "\\".to_string(),
+                    line,
+                    column: source
+                        .chars()
+                        .count(),
+                    source: source.clone(),
+                },
+            });
+        match snippet_line_at(&curr_line, &origins, &sources, &mut parsed) {
+            Ok(()) => line_numbers.push(*sources
+                .first()
+                .unwrap()
+                .0),
+            Err(error) => errors.push(error),
+        }
     }
 
-    Ok(Snippet { lines, defaults })
+    let Parsed {
+        metadata,
+        declared,
+        declared_defaults: _,
+        lines,
+        defaults,
+        types,
+        help,
+        includes,
+        mut diagnostics,
+        conditions,
+    } = parsed;
+
+    for token in conditions {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            message: format!(
+                "Condition `{}` is never ended with `$^$`, \
+                it lasts until the end of the snippet",
+                token.text
+            ),
+            token,
+        });
+    }
+
+    Ok((
+        Snippet {
+            metadata,
+            declared,
+            lines,
+            line_numbers,
+            defaults,
+            types,
+            help,
+            includes,
+            diagnostics,
+        },
+        errors,
+    ))
+}
+
+/// Read the snippet as-is, without interpreting any `$` sequences or comments.
+pub fn raw(snippet: Box<dyn Read>) -> io::Result<Snippet> {
+    let mut lines = Vec::new();
+    for input in BufReader::new(snippet).lines() {
+        lines.push(vec![LinePart::Text(input?)]);
+    }
+
+    Ok(Snippet {
+        metadata: Metadata::default(),
+        declared: Vec::new(),
+        line_numbers: (1..=lines.len()).collect(),
+        lines,
+        defaults: IndexMap::new(),
+        types: IndexMap::new(),
+        help: IndexMap::new(),
+        includes: Vec::new(),
+        diagnostics: Vec::new(),
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Parse a single line, as if it was the first line of a snippet.
+    fn snippet_line(
+        curr_line: &str,
+        lines: &mut Vec<Vec<LinePart>>,
+        defaults: &mut IndexMap<String, String>,
+    ) -> Result<(), Error> {
+        let origins = (1..=curr_line
+            .chars()
+            .count())
+            .map(|column| (1, column))
+            .collect::<Vec<_>>();
+        let sources = IndexMap::from([(1, curr_line.to_string())]);
+
+        let mut parsed = Parsed {
+            lines: std::mem::take(lines),
+            defaults: std::mem::take(defaults),
+            ..Default::default()
+        };
+        let result = snippet_line_at(curr_line, &origins, &sources, &mut parsed);
+        *lines = parsed.lines;
+        *defaults = parsed.defaults;
+        result
+    }
+
     #[test]
     fn test_snippet_line() {
         let text = "test $@arg1::a$ $@$ $@arg2$";
@@ -320,9 +1522,9 @@ mod tests {
             lines,
             [[
                 Text("test ".to_string()),
-                Arg("arg1".to_string()),
+                Arg("arg1".to_string(), vec![]),
                 Text(" $@ ".to_string()),
-                Arg("arg2".to_string())
+                Arg("arg2".to_string(), vec![])
             ]]
         );
         assert_eq!(
@@ -340,7 +1542,13 @@ mod tests {
             test $@arg1$ $@arg2::a$ $@$ $@arg3$\n\
             test $@arg3$ $ $@arg1::b$ $@arg2$";
 
-        let Snippet { lines, defaults } = snippet(Box::new(text.as_bytes())).unwrap();
+        let Snippet {
+            lines,
+            line_numbers,
+            defaults,
+            diagnostics,
+            ..
+        } = snippet(Box::new(text.as_bytes())).unwrap();
 
         use LinePart::*;
         assert_eq!(
@@ -348,19 +1556,19 @@ mod tests {
             [
                 &[
                     Text("test ".to_string()),
-                    Arg("arg1".to_string()),
+                    Arg("arg1".to_string(), vec![]),
                     Text(" ".to_string()),
-                    Arg("arg2".to_string()),
+                    Arg("arg2".to_string(), vec![]),
                     Text(" $@ ".to_string()),
-                    Arg("arg3".to_string()),
+                    Arg("arg3".to_string(), vec![]),
                 ][..],
                 &[
                     Text("test ".to_string()),
-                    Arg("arg3".to_string()),
+                    Arg("arg3".to_string(), vec![]),
                     Text(" $ ".to_string()),
-                    Arg("arg1".to_string()),
+                    Arg("arg1".to_string(), vec![]),
                     Text(" ".to_string()),
-                    Arg("arg2".to_string()),
+                    Arg("arg2".to_string(), vec![]),
                 ]
             ]
         );
@@ -377,5 +1585,529 @@ mod tests {
                 )
             ])
         );
+        assert_eq!(line_numbers, [1, 2]);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_snippet_diagnostics() {
+        let text = "\
+            $@x::a$ $@x::b$\n\
+            echo $@y\n\
+            kill $$ $$$$ $$@\n\
+            xdotool $!Alt+Tab \\";
+
+        let Snippet { diagnostics, .. } = snippet(Box::new(text.as_bytes())).unwrap();
+
+        let found = diagnostics
+            .iter()
+            .map(|d| {
+                (
+                    d.severity,
+                    d.token
+                        .text
+                        .as_str(),
+                    d.token.line,
+                    d.token.column,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                (Severity::Warning, "$@x::b$", 1, 9),
+                (Severity::Warning, "$@y", 2, 6),
+                (Severity::Warning, "$$", 3, 6),
+                (Severity::Warning, "\\", 4, 19),
+                (Severity::Warning, "$!Alt+Tab ", 4, 9),
+            ]
+        );
+        assert!(diagnostics[0]
+            .message
+            .starts_with("Duplicate default value for argument `x`"));
+    }
+
+    #[test]
+    fn test_snippet_line_arglist() {
+        let text = "docker run -p $[port::80$ $[$ $@image$";
+
+        let mut lines = Vec::new();
+        let mut defaults = IndexMap::new();
+        snippet_line(
+            text,
+            &mut lines,
+            &mut defaults,
+        )
+        .unwrap();
+
+        use LinePart::*;
+        assert_eq!(
+            lines,
+            [[
+                Text("docker run -p ".to_string()),
+                ArgList("port".to_string(), vec![]),
+                Text(" $[ ".to_string()),
+                Arg("image".to_string(), vec![])
+            ]]
+        );
+        assert_eq!(
+            defaults,
+            IndexMap::from([(
+                "port".to_string(),
+                "80".to_string()
+            )])
+        );
+    }
+
+    #[test]
+    fn test_snippet_line_escape() {
+        let text = "echo $$'a\\tb' $$$@x$ $$@ $$$$ $!$";
+
+        let mut lines = Vec::new();
+        let mut defaults = IndexMap::new();
+        snippet_line(
+            text,
+            &mut lines,
+            &mut defaults,
+        )
+        .unwrap();
+
+        use LinePart::*;
+        assert_eq!(
+            lines,
+            [[
+                Text("echo $'a\\tb' $".to_string()),
+                Arg("x".to_string(), vec![]),
+                Text(" $@ $$ $!".to_string()),
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_snippet_conditions() {
+        let text = "\
+            $^proxy$--proxy $@proxy$ $^$$^distro=debian$\n\
+            apt update\n\
+            $^$\n\
+            $^$ $$^x$";
+
+        let Snippet {
+            lines,
+            diagnostics,
+            ..
+        } = snippet(Box::new(text.as_bytes())).unwrap();
+
+        use LinePart::*;
+        assert_eq!(
+            lines[0],
+            [
+                Text(String::new()),
+                If(Condition {
+                    arg: "proxy".to_string(),
+                    value: None
+                }),
+                Text("--proxy ".to_string()),
+                Arg("proxy".to_string(), vec![]),
+                Text(" ".to_string()),
+                EndIf,
+                Text(String::new()),
+                If(Condition {
+                    arg: "distro".to_string(),
+                    value: Some("debian".to_string())
+                }),
+            ]
+        );
+        assert_eq!(
+            lines[3],
+            [
+                Text(String::new()),
+                EndIf,
+                Text(" $^x$".to_string())
+            ]
+        );
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| (d.token.line, d.token.column))
+                .collect::<Vec<_>>(),
+            [(4, 1)]
+        );
+    }
+
+    #[test]
+    fn test_settings() {
+        assert_eq!(
+            settings("char=50 line=default").unwrap(),
+            [
+                Setting::CharDelay(Some(50)),
+                Setting::LineDelay(None)
+            ]
+        );
+        assert_eq!(
+            settings("eol=Control+j,final-eol=none").unwrap(),
+            [
+                Setting::LineEnd(Some(LineEnd::Keys(vec![
+                    enigo::Key::Control,
+                    enigo::Key::Unicode('j')
+                ]))),
+                Setting::FinalLineEnd(Some(LineEnd::Nothing))
+            ]
+        );
+        assert_eq!(
+            settings("eol=Ctrl+Enter").unwrap(),
+            [Setting::LineEnd(Some(LineEnd::Keys(vec![
+                enigo::Key::Control,
+                enigo::Key::Return
+            ])))]
+        );
+        assert!(settings("char=fast").is_err());
+        assert!(settings("speed=50").is_err());
+    }
+
+    #[test]
+    fn test_snippet_types() {
+        let text = "\
+            ssh -p $@port:port::22$ $@host:hostname$\n\
+            apt install $@pkg$ $[distro:debian|arch::arch$ $@port$ $@port:int$";
+
+        let Snippet {
+            lines,
+            defaults,
+            types,
+            diagnostics,
+            ..
+        } = snippet(Box::new(text.as_bytes())).unwrap();
+
+        use LinePart::*;
+        assert_eq!(
+            lines[1],
+            [
+                Text("apt install ".to_string()),
+                Arg("pkg".to_string(), vec![]),
+                Text(" ".to_string()),
+                ArgList("distro".to_string(), vec![]),
+                Text(" ".to_string()),
+                Arg("port".to_string(), vec![]),
+                Text(" ".to_string()),
+                Arg("port".to_string(), vec![])
+            ]
+        );
+        assert_eq!(defaults["port"], "22");
+        assert_eq!(
+            types,
+            IndexMap::from([
+                ("port".to_string(), ArgType::Integer),
+                ("host".to_string(), ArgType::Hostname),
+                (
+                    "distro".to_string(),
+                    ArgType::Choice(vec![
+                        "debian".to_string(),
+                        "arch".to_string()
+                    ])
+                ),
+            ])
+        );
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| (d.token.line, d.token.column))
+                .collect::<Vec<_>>(),
+            [(2, 56)]
+        );
+
+        // a `:` not followed by a known type is a part of the name
+        let text = "at $@port:number$ $@Time (hh:mm)$ $@URL (e.g. https://host):hostname$";
+        let Snippet { lines, types, .. } = snippet(Box::new(text.as_bytes())).unwrap();
+        assert_eq!(
+            lines[0],
+            [
+                Text("at ".to_string()),
+                Arg("port:number".to_string(), vec![]),
+                Text(" ".to_string()),
+                Arg("Time (hh:mm)".to_string(), vec![]),
+                Text(" ".to_string()),
+                Arg("URL (e.g. https://host)".to_string(), vec![])
+            ]
+        );
+        assert_eq!(
+            types,
+            IndexMap::from([(
+                "URL (e.g. https://host)".to_string(),
+                ArgType::Hostname
+            )])
+        );
+
+        let text = "$@port::ssh$ $@port:port$";
+        let Err(Error::InvalidDefault(token, _)) = snippet(Box::new(text.as_bytes())) else {
+            panic!("expected invalid default");
+        };
+        assert_eq!(token.column, 14);
+    }
+
+    #[test]
+    fn test_snippet_filters() {
+        let text = "echo $@msg|trim|shquote::hi there$ $[tag|upper:a|b::a$";
+
+        let Snippet {
+            lines, defaults, types, ..
+        } = snippet(Box::new(text.as_bytes())).unwrap();
+
+        use LinePart::*;
+        assert_eq!(
+            lines,
+            [[
+                Text("echo ".to_string()),
+                Arg(
+                    "msg".to_string(),
+                    vec![Filter::Trim, Filter::ShellQuote]
+                ),
+                Text(" ".to_string()),
+                ArgList("tag".to_string(), vec![Filter::Upper])
+            ]]
+        );
+        assert_eq!(defaults["msg"], "hi there");
+        assert_eq!(
+            types["tag"],
+            ArgType::Choice(vec!["a".to_string(), "b".to_string()])
+        );
+
+        // a `|` not followed by a known filter is a part of the name
+        let text = "echo $@yes|no$ $@Mode (a|b)|upper$";
+        let Snippet { lines, .. } = snippet(Box::new(text.as_bytes())).unwrap();
+        assert_eq!(
+            lines[0],
+            [
+                Text("echo ".to_string()),
+                Arg("yes|no".to_string(), vec![]),
+                Text(" ".to_string()),
+                Arg("Mode (a|b)".to_string(), vec![Filter::Upper])
+            ]
+        );
+
+        let text = "echo $%HOME|reverse$";
+        let Err(Error::UnknownFilter(_, filter)) = snippet(Box::new(text.as_bytes())) else {
+            panic!("expected unknown filter");
+        };
+        assert_eq!(filter, "reverse");
+    }
+
+    #[test]
+    fn test_snippet_help() {
+        let text = "\
+            ##? Host: Database server to connect to | db1.example.com\n\
+            ##? Port:  Port of the server\n\
+            ##? no colon $@here$\n\
+            psql -h $@Host$ -p $@Port$";
+
+        let Snippet {
+            lines,
+            help,
+            diagnostics,
+            ..
+        } = snippet(Box::new(text.as_bytes())).unwrap();
+
+        assert_eq!(
+            help,
+            IndexMap::from([
+                (
+                    "Host".to_string(),
+                    ArgHelp {
+                        description: "Database server to connect to".to_string(),
+                        example: Some("db1.example.com".to_string()),
+                    }
+                ),
+                (
+                    "Port".to_string(),
+                    ArgHelp {
+                        description: "Port of the server".to_string(),
+                        example: None,
+                    }
+                ),
+            ])
+        );
+        assert_eq!(
+            help["Host"].to_string(),
+            "Database server to connect to (e.g. `db1.example.com`)"
+        );
+        assert!(lines[..3]
+            .iter()
+            .all(|line| is_comment(line)));
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| d.token.line)
+                .collect::<Vec<_>>(),
+            [3]
+        );
+    }
+
+    #[test]
+    fn test_snippet_includes() {
+        let text = "\
+            ##include common/proxy.txt\n\
+            ##included for legacy hosts\n\
+            ##include\n\
+            echo $@host$";
+
+        let Snippet {
+            lines,
+            includes,
+            diagnostics,
+            ..
+        } = snippet(Box::new(text.as_bytes())).unwrap();
+
+        assert_eq!(includes, [(1, "common/proxy.txt".to_string())]);
+        assert!(lines[..3]
+            .iter()
+            .all(|line| is_comment(line)));
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| d.token.line)
+                .collect::<Vec<_>>(),
+            [3]
+        );
+    }
+
+    #[test]
+    fn test_snippet_front_matter() {
+        let text = "\
+            +++\n\
+            name = \"Deploy\"\n\
+            [args.Port]\n\
+            type = \"port\"\n\
+            default = \"80\"\n\
+            [args.Unused]\n\
+            +++\n\
+            docker run -p $[Port::8080$ $@Image$";
+
+        let Snippet {
+            metadata,
+            declared,
+            lines,
+            line_numbers,
+            defaults,
+            types,
+            diagnostics,
+            ..
+        } = snippet(Box::new(text.as_bytes())).unwrap();
+
+        assert_eq!(metadata.name, Some("Deploy".to_string()));
+        assert_eq!(
+            declared
+                .iter()
+                .map(|(name, token)| (name.as_str(), token.line))
+                .collect::<Vec<_>>(),
+            [("Port", 3), ("Unused", 6)]
+        );
+        assert_eq!(line_numbers, [8]);
+        assert_eq!(defaults["Port"], "8080");
+        assert_eq!(types["Port"], ArgType::Port);
+        // the inline default overrides the declared one without a warning
+        assert!(diagnostics.is_empty());
+        assert_eq!(
+            unused_declarations(&lines, &declared)
+                .iter()
+                .map(|d| d.token.line)
+                .collect::<Vec<_>>(),
+            [6]
+        );
+
+        let Err(Error::InvalidDefault(..)) =
+            snippet(Box::new("+++\n[args.Port]\ntype = \"port\"\n+++\n$@Port::http$".as_bytes()))
+        else {
+            panic!("default should be checked against the declared type");
+        };
+        let Err(Error::InvalidFrontMatter(token, _)) = snippet(Box::new("+++\necho".as_bytes()))
+        else {
+            panic!("front matter should be ended");
+        };
+        assert_eq!(token.line, 1);
+    }
+
+    #[test]
+    fn test_snippet_errors() {
+        let text = "\
+            echo $@x$\n\
+            xdotool $!Control+Shfit$ \\\n  \
+            $'1s$";
+
+        let Err(Error::UnknownKey(token)) = snippet(Box::new(text.as_bytes())) else {
+            panic!("expected unknown key");
+        };
+        assert_eq!(
+            token,
+            Token {
+                text: "Shfit".to_string(),
+                line: 2,
+                column: 19,
+                source: "xdotool $!Control+Shfit$ \\".to_string(),
+            }
+        );
+        assert_eq!(
+            token.to_string(),
+            "\
+            2 | xdotool $!Control+Shfit$ \\\n  \
+            |                   ^^^^^"
+        );
+
+        let text = "xdotool \\\n  $'1s$";
+        let Err(Error::InvalidDelay(token)) = snippet(Box::new(text.as_bytes())) else {
+            panic!("expected invalid delay");
+        };
+        assert_eq!(
+            (token.text.as_str(), token.line, token.column),
+            ("$'1s$", 2, 3)
+        );
+
+        let text = "$~char=50 line=slow$";
+        let Err(Error::InvalidSettings(token, _)) = snippet(Box::new(text.as_bytes())) else {
+            panic!("expected invalid settings");
+        };
+        assert_eq!(
+            (token.text.as_str(), token.line, token.column),
+            ("$~char=50 line=slow$", 1, 1)
+        );
+
+        let text = "$'x$\na \\\nb\n$!Shfit$";
+        let (Snippet { line_numbers, .. }, errors) =
+            snippet_with_errors(Box::new(text.as_bytes())).unwrap();
+        assert_eq!(line_numbers, [2]);
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| e
+                    .diagnostic()
+                    .unwrap()
+                    .token
+                    .line)
+                .collect::<Vec<_>>(),
+            [1, 4]
+        );
+    }
+
+    #[test]
+    fn test_raw() {
+        let text = "\
+            ## $@arg1::a$ \\\n\
+            $!Alt+Tab$ $'100$ $@$";
+
+        let Snippet {
+            lines,
+            defaults,
+            diagnostics,
+            ..
+        } = raw(Box::new(text.as_bytes())).unwrap();
+
+        use LinePart::*;
+        assert_eq!(
+            lines,
+            [
+                [Text("## $@arg1::a$ \\".to_string())],
+                [Text("$!Alt+Tab$ $'100$ $@$".to_string())],
+            ]
+        );
+        assert!(defaults.is_empty());
+        assert!(diagnostics.is_empty());
     }
 }
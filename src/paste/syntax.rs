@@ -0,0 +1,478 @@
+use std::ops::Range;
+
+use super::front_matter;
+use super::parse;
+use super::parse::HELP_PREFIX;
+
+/// Snippet split into nodes that cover all of its source, so it can be rewritten and written back.
+///
+/// Unlike `parse::snippet`, nothing is dropped: comments, line continuations, escapes and the
+/// exact spelling of every token are kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tree {
+    pub source: String,
+    pub nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub kind: NodeKind,
+    /// Byte range of the node in the source.
+    pub span: Range<usize>,
+    /// `false` for tokens without their closing `$`.
+    pub complete: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeKind {
+    Text,
+    /// `$$`, or one of `$@$`, `$[$`, `$!$`, `$~$`, `$%$` and `$<$`.
+    Escape,
+    /// `$'...$`
+    Delay,
+    /// `$@name|filter:type::default$`, or `$[name|filter:type::default$` for arglists.
+    Arg {
+        name: String,
+        /// Filters as written, after `|`s.
+        filters: Vec<String>,
+        /// Declared type, after a single `:`.
+        kind: Option<String>,
+        default: Option<String>,
+        list: bool,
+    },
+    /// `$!Key+Key$`, with the key names as written.
+    KeyCombo(Vec<String>),
+    /// `$~...$`
+    Settings,
+    /// `$^...$`, or `$^$` ending the conditional part.
+    Condition,
+    /// `$%NAME::default$`
+    Env,
+    /// `$<command$`
+    Command,
+    /// `##? Name: description | example` line, with nothing in it parsed.
+    Help,
+    /// `##include target` line, with nothing in it parsed.
+    Include,
+    /// The front matter with its delimiters, which is not made of tokens.
+    FrontMatter,
+    /// Backslash at the end of a line, the line break and the indentation of the next line.
+    Continuation,
+    Newline,
+}
+
+enum State {
+    Text,
+    Delay,
+    Arg {
+        name: String,
+        default: Option<String>,
+        list: bool,
+    },
+    KeyCombo(Vec<String>),
+    Settings(String),
+    Condition,
+    Env(String),
+    Command(String),
+    Help,
+    Include,
+}
+
+/// Length of the line break at the start of `rest`, if there is one.
+fn line_break(rest: &str) -> Option<usize> {
+    match rest {
+        _ if rest.starts_with('\n') => Some(1),
+        _ if rest.starts_with("\r\n") => Some(2),
+        _ => None,
+    }
+}
+
+/// End of the front matter at the start of the source (before the line break of its last
+/// delimiter), and whether it is ended at all.
+fn front_matter_end(source: &str) -> Option<(usize, bool)> {
+    let mut lines = source.split_inclusive('\n');
+    let first = lines.next()?;
+    if first.trim_end() != front_matter::DELIMITER {
+        return None;
+    }
+
+    let mut end = first.len();
+    for line in lines {
+        if line.trim_end() == front_matter::DELIMITER {
+            return Some((end + line.trim_end_matches(['\r', '\n']).len(), true));
+        }
+        end += line.len();
+    }
+    Some((source.len(), false))
+}
+
+impl Tree {
+    /// Split the source the same way `parse::snippet` reads it.
+    pub fn parse(source: &str) -> Self {
+        let mut nodes = Vec::new();
+        let mut state = State::Text;
+        // start of the node being read
+        let mut start = 0;
+        let mut i = 0;
+
+        // turn the state into a node ending at `end`
+        let close = |nodes: &mut Vec<Node>, state: State, start: usize, end: usize, complete| {
+            let kind = match state {
+                State::Text if start == end => return,
+                State::Text => NodeKind::Text,
+                State::Delay => NodeKind::Delay,
+                State::Arg {
+                    name,
+                    default,
+                    list,
+                } => {
+                    let (name, filters, kind) = parse::split_arg(&name);
+                    NodeKind::Arg {
+                        name: name.to_string(),
+                        filters: filters
+                            .into_iter()
+                            .map(str::to_string)
+                            .collect(),
+                        kind: kind.map(str::to_string),
+                        default,
+                        list,
+                    }
+                },
+                State::KeyCombo(keys) => NodeKind::KeyCombo(keys),
+                State::Settings(_) => NodeKind::Settings,
+                State::Condition => NodeKind::Condition,
+                State::Env(_) => NodeKind::Env,
+                State::Command(_) => NodeKind::Command,
+                State::Help => NodeKind::Help,
+                State::Include => NodeKind::Include,
+            };
+            nodes.push(Node {
+                kind,
+                span: start..end,
+                complete,
+            });
+        };
+
+        if let Some((end, complete)) = front_matter_end(source) {
+            nodes.push(Node {
+                kind: NodeKind::FrontMatter,
+                span: 0..end,
+                complete,
+            });
+            start = end;
+            i = end;
+        }
+
+        while let Some(ch) = source[i..]
+            .chars()
+            .next()
+        {
+            let rest = &source[i..];
+            let next = rest[ch.len_utf8()..]
+                .chars()
+                .next();
+
+            // `\` at the end of a line joins the next one, without its leading whitespace
+            if ch == '\\' && (next.is_none() || line_break(&rest[1..]).is_some()) {
+                let mut end = i + 1 + line_break(&rest[1..]).unwrap_or(0);
+                if end > i + 1 {
+                    end += source[end..]
+                        .chars()
+                        .take_while(|ch| ch.is_whitespace() && *ch != '\n')
+                        .map(char::len_utf8)
+                        .sum::<usize>();
+                    // `\r` before `\n` belongs to the line break
+                    if source[..end].ends_with('\r') && source[end..].starts_with('\n') {
+                        end -= 1;
+                    }
+                }
+
+                // tokens continue over the joined lines
+                if let State::Text = state {
+                    close(&mut nodes, State::Text, start, i, true);
+                    nodes.push(Node {
+                        kind: NodeKind::Continuation,
+                        span: i..end,
+                        complete: true,
+                    });
+                    start = end;
+                }
+                i = end;
+                continue;
+            }
+
+            // line ends, and so does everything unfinished on it
+            if let Some(length) = line_break(rest) {
+                let complete = matches!(state, State::Text | State::Help | State::Include);
+                close(
+                    &mut nodes,
+                    std::mem::replace(&mut state, State::Text),
+                    start,
+                    i,
+                    complete,
+                );
+                nodes.push(Node {
+                    kind: NodeKind::Newline,
+                    span: i..i + length,
+                    complete: true,
+                });
+                i += length;
+                start = i;
+                continue;
+            }
+
+            // help and include lines start at the start of a line, not of a joined one
+            let line_start = start == i
+                && matches!(
+                    nodes.last(),
+                    None | Some(Node {
+                        kind: NodeKind::Newline,
+                        ..
+                    })
+                );
+
+            let mut end = i + ch.len_utf8();
+            match &mut state {
+                State::Text if line_start && rest.starts_with(HELP_PREFIX) => state = State::Help,
+                State::Text if line_start && parse::include(rest).is_some() =>
+                    state = State::Include,
+                State::Text if ch == '$' && next == Some('$') => {
+                    close(&mut nodes, State::Text, start, i, true);
+                    nodes.push(Node {
+                        kind: NodeKind::Escape,
+                        span: i..i + 2,
+                        complete: true,
+                    });
+                    end += 1;
+                    start = end;
+                },
+                State::Text
+                    if ch == '$' && matches!(next, Some('\'' | '@' | '[' | '!' | '~' | '^' | '%' | '<')) =>
+                {
+                    close(&mut nodes, State::Text, start, i, true);
+                    state = match next {
+                        Some('\'') => State::Delay,
+                        Some('@') => State::Arg {
+                            name: String::new(),
+                            default: None,
+                            list: false,
+                        },
+                        Some('[') => State::Arg {
+                            name: String::new(),
+                            default: None,
+                            list: true,
+                        },
+                        Some('!') => State::KeyCombo(vec![String::new()]),
+                        Some('~') => State::Settings(String::new()),
+                        Some('%') => State::Env(String::new()),
+                        Some('<') => State::Command(String::new()),
+                        _ => State::Condition,
+                    };
+                    end += 1;
+                    start = i;
+                },
+                State::Text => {},
+
+                State::Arg { name, .. } if ch == '$' && name.is_empty() => {
+                    state = State::Text;
+                    nodes.push(Node {
+                        kind: NodeKind::Escape,
+                        span: start..end,
+                        complete: true,
+                    });
+                    start = end;
+                },
+                State::Arg {
+                    default: default @ None,
+                    ..
+                } if ch == ':' && next == Some(':') => {
+                    *default = Some(String::new());
+                    end += 1;
+                },
+                State::Arg {
+                    name,
+                    default: None,
+                    ..
+                } if ch != '$' => name.push(ch),
+                State::Arg {
+                    default: Some(default),
+                    ..
+                } if ch != '$' => default.push(ch),
+
+                State::KeyCombo(keys) if ch == '+' || ch == ' ' => {
+                    if keys
+                        .last()
+                        .is_some_and(|key| !key.is_empty())
+                    {
+                        keys.push(String::new());
+                    }
+                },
+                State::KeyCombo(keys) if ch == '$' && keys == &[""] => {
+                    state = State::Text;
+                    nodes.push(Node {
+                        kind: NodeKind::Escape,
+                        span: start..end,
+                        complete: true,
+                    });
+                    start = end;
+                },
+                State::KeyCombo(keys) if ch != '$' => keys
+                    .last_mut()
+                    .unwrap()
+                    .push(ch),
+
+                State::Settings(settings) if ch == '$' && settings.is_empty() => {
+                    state = State::Text;
+                    nodes.push(Node {
+                        kind: NodeKind::Escape,
+                        span: start..end,
+                        complete: true,
+                    });
+                    start = end;
+                },
+                State::Settings(settings) if ch != '$' => settings.push(ch),
+
+                State::Env(text) | State::Command(text) if ch == '$' && text.is_empty() => {
+                    state = State::Text;
+                    nodes.push(Node {
+                        kind: NodeKind::Escape,
+                        span: start..end,
+                        complete: true,
+                    });
+                    start = end;
+                },
+                State::Env(text) | State::Command(text) if ch != '$' => text.push(ch),
+
+                State::Delay | State::Condition if ch != '$' => {},
+                State::Help | State::Include => {},
+
+                // closing `$` of a token
+                _ => {
+                    close(
+                        &mut nodes,
+                        std::mem::replace(&mut state, State::Text),
+                        start,
+                        end,
+                        true,
+                    );
+                    start = end;
+                },
+            }
+            i = end;
+        }
+
+        let complete = matches!(state, State::Text | State::Help | State::Include);
+        close(
+            &mut nodes,
+            state,
+            start,
+            source.len(),
+            complete,
+        );
+
+        Self {
+            source: source.to_string(),
+            nodes,
+        }
+    }
+
+    /// Source text of the node.
+    pub fn text(&self, node: &Node) -> &str { &self.source[node.span.clone()] }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tree() {
+        let source = "\
+            +++\n\
+            name = \"$@not-an-arg$\"\n\
+            +++\n\
+            ##? host: Server to connect to | $@host$\n\
+            ##include ../$@common$.txt\n\
+            ##included $@x$\n\
+            ## $@host::example.com$ $$ $@$\r\n\
+            ssh $[port:port::22$ \\\n    \t$!ctrl + c$ $'10$\\\n\
+            \x20 $~char=5$ $^a=b$$^$ $%HOME::/root$$<id -u$ $@incomplete\n\
+            $@split\\\n  name$ \\";
+
+        let tree = Tree::parse(source);
+        assert_eq!(
+            tree.nodes
+                .iter()
+                .map(|node| tree.text(node))
+                .collect::<String>(),
+            source
+        );
+
+        use NodeKind::*;
+        let kinds = tree
+            .nodes
+            .iter()
+            .map(|node| (&node.kind, tree.text(node), node.complete))
+            .collect::<Vec<_>>();
+        let arg = |name: &str, kind: Option<&str>, default: Option<&str>, list| Arg {
+            name: name.to_string(),
+            filters: Vec::new(),
+            kind: kind.map(str::to_string),
+            default: default.map(str::to_string),
+            list,
+        };
+        assert_eq!(
+            kinds,
+            [
+                (&FrontMatter, "+++\nname = \"$@not-an-arg$\"\n+++", true),
+                (&Newline, "\n", true),
+                (&Help, "##? host: Server to connect to | $@host$", true),
+                (&Newline, "\n", true),
+                (&Include, "##include ../$@common$.txt", true),
+                (&Newline, "\n", true),
+                (&Text, "##included ", true),
+                (&arg("x", None, None, false), "$@x$", true),
+                (&Newline, "\n", true),
+                (&Text, "## ", true),
+                (
+                    &arg("host", None, Some("example.com"), false),
+                    "$@host::example.com$",
+                    true
+                ),
+                (&Text, " ", true),
+                (&Escape, "$$", true),
+                (&Text, " ", true),
+                (&Escape, "$@$", true),
+                (&Newline, "\r\n", true),
+                (&Text, "ssh ", true),
+                (
+                    &arg("port", Some("port"), Some("22"), true),
+                    "$[port:port::22$",
+                    true
+                ),
+                (&Text, " ", true),
+                (&Continuation, "\\\n    \t", true),
+                (
+                    &KeyCombo(vec!["ctrl".to_string(), "c".to_string()]),
+                    "$!ctrl + c$",
+                    true
+                ),
+                (&Text, " ", true),
+                (&Delay, "$'10$", true),
+                (&Continuation, "\\\n  ", true),
+                (&Settings, "$~char=5$", true),
+                (&Text, " ", true),
+                (&Condition, "$^a=b$", true),
+                (&Condition, "$^$", true),
+                (&Text, " ", true),
+                (&Env, "$%HOME::/root$", true),
+                (&Command, "$<id -u$", true),
+                (&Text, " ", true),
+                (&arg("incomplete", None, None, false), "$@incomplete", false),
+                (&Newline, "\n", true),
+                (&arg("splitname", None, None, false), "$@split\\\n  name$", true),
+                (&Text, " ", true),
+                (&Continuation, "\\", true),
+            ]
+        );
+    }
+}
@@ -3,7 +3,9 @@ use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fs::File;
 use std::io;
+use std::io::stdin;
 use std::io::stdout;
+use std::io::Cursor;
 use std::io::Read;
 use std::io::Seek;
 use std::io::Write;
@@ -20,6 +22,7 @@ use tempfile::tempfile;
 use url::Url;
 
 use crate::ask;
+use crate::clipboard;
 use crate::USER_AGENT;
 
 #[derive(Debug, Clone)]
@@ -32,6 +35,9 @@ pub enum Error {
 
     URLFormat,
     PathIsNotFile,
+    IncludeFromRemote(String),
+
+    ClipboardError(String),
 
     NetworkError(Rc<::reqwest::Error>),
     IOError(Rc<io::Error>),
@@ -58,6 +64,11 @@ impl Display for Error {
                 f,
                 "Path does not exist or does not point to a file"
             ),
+            IncludeFromRemote(path) => write!(
+                f,
+                "Snippets from the internet cannot include local file `{path}`"
+            ),
+            ClipboardError(e) => write!(f, "{e}"),
             NetworkError(e) => write!(f, "Network error: {e}"),
             IOError(e) => write!(f, "IO error: {e}"),
             CancelledRun => write!(f, "Cancelled"),
@@ -86,10 +97,90 @@ pub struct Gist {
     files: IndexMap<String, GistFile>,
 }
 
+/// Whether the target is loaded from the internet (`github:`, `gist:` or a URL).
+pub fn is_remote(target: &str) -> bool {
+    let target = target.trim();
+    ["github:", "gist:", "https://", "http://"]
+        .iter()
+        .any(|prefix| target.starts_with(prefix))
+}
+
+/// Resolve `target`, included by the `base` snippet, relative to it.
+pub fn join(base: &str, target: &str) -> Result<String, Error> {
+    use Error::*;
+    let base = base.trim();
+    let target = target.trim();
+
+    if is_remote(target) {
+        return Ok(target.to_string());
+    }
+    if Path::new(target).is_absolute() {
+        return match is_remote(base) {
+            true => Err(IncludeFromRemote(target.to_string())),
+            false => Ok(target.to_string()),
+        };
+    }
+
+    // GitHub: next to the file, on the same branch
+    if let Some(rest) = base.strip_prefix("github:") {
+        let (path, branch) = match rest.split_once('#') {
+            Some((path, branch)) => (path, format!("#{branch}")),
+            None => (rest, String::new()),
+        };
+        let dir = path
+            .rsplit_once('/')
+            .ok_or(GitHubFormat)?
+            .0;
+        return Ok(format!("github:{dir}/{target}{branch}"));
+    }
+
+    // Gist: another file of the same gist, or of the same user
+    if let Some(rest) = base.strip_prefix("gist:") {
+        return Ok(match rest.split_once('/') {
+            None => {
+                let gist_id = rest
+                    .split_once('#')
+                    .map_or(rest, |(gist_id, _)| gist_id);
+                format!("gist:{gist_id}#{target}")
+            },
+            Some((user, _)) => format!("gist:{user}/{target}"),
+        });
+    }
+
+    if is_remote(base) {
+        return Ok(Url::parse(base)
+            .and_then(|url| url.join(target))
+            .map_err(|_| URLFormat)?
+            .to_string());
+    }
+
+    // stdin and the clipboard have no directory, so the target is relative to the current one
+    if base == "-" || base == "!" {
+        return Ok(target.to_string());
+    }
+    Ok(match Path::new(base).parent() {
+        Some(dir) => dir
+            .join(target)
+            .to_string_lossy()
+            .into_owned(),
+        None => target.to_string(),
+    })
+}
+
 pub fn parse_and_load(target: &str) -> Result<Box<dyn Read>, Error> {
     use Error::*;
     let target = target.trim();
 
+    // Stdin: `-`
+    if target == "-" {
+        return Ok(Box::new(stdin()));
+    }
+
+    // Clipboard: `!`
+    if target == "!" {
+        return target_from_clipboard();
+    }
+
     let client = Client::new();
 
     // GitHub: `github:{user}/{repo}/{filepath}(#{branch})`
@@ -224,6 +315,11 @@ fn target_from_path(target: impl AsRef<Path>) -> Result<Box<dyn Read>, Error> {
     Ok(Box::new(file))
 }
 
+fn target_from_clipboard() -> Result<Box<dyn Read>, Error> {
+    let text = clipboard::get_text().map_err(|e| Error::ClipboardError(e.to_string()))?;
+    Ok(Box::new(Cursor::new(text.into_bytes())))
+}
+
 fn target_from_url(client: Client, target: Url) -> Result<Box<dyn Read>, Error> {
     let mut response = client
         .get(target.clone())
@@ -1,6 +1,4 @@
 use std::error::Error;
-use std::fs::File;
-use std::io;
 use std::io::stdout;
 use std::io::Cursor;
 use std::io::Write;
@@ -11,6 +9,7 @@ use image::ImageFormat::Bmp;
 use image::ImageFormat::Png;
 
 use crate::ask;
+use crate::create_file;
 
 pub fn main(args: impl IntoIterator<Item = String>) -> Result<(), Box<dyn Error>> {
     let mut args = args.into_iter();
@@ -55,20 +54,10 @@ pub fn main(args: impl IntoIterator<Item = String>) -> Result<(), Box<dyn Error>
 
     let mut output: Box<dyn Write> = match &*output {
         "" | "-" => Box::new(stdout()),
-        path => Box::new(
-            match File::create_new(&path) {
-                Ok(file) => file,
-                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
-                    let answer = ask("File already exists. Overwrite? (`y` / anything else)")?
-                        .to_lowercase();
-                    if answer != "y" && answer != "yes" {
-                        return Ok(());
-                    }
-                    File::create(&path)?
-                },
-                Err(err) => return Err(err.into()),
-            },
-        ),
+        path => match create_file(path)? {
+            Some(file) => Box::new(file),
+            None => return Ok(()),
+        },
     };
     output.write_all(&text)?;
 
@@ -0,0 +1,177 @@
+use std::io;
+use std::io::stdin;
+use std::io::stdout;
+use std::io::IsTerminal;
+use std::io::Write;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::ptr;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+use indexmap::IndexMap;
+
+/// Shown instead of secret values, the same for every value so that not even the length is shown.
+pub const MASK: &str = "********";
+
+/// Whether echo was turned off and has to be turned back on, even if the program is cancelled.
+static ECHO_OFF: AtomicBool = AtomicBool::new(false);
+
+/// Like `ask_line`, but the answer is not echoed to the terminal.
+pub fn ask(question: &str) -> io::Result<Option<String>> {
+    print!("{}: ", question);
+    stdout().flush()?;
+
+    // piped input is not echoed anyway
+    let terminal = stdin().is_terminal();
+    if terminal {
+        set_echo(false)?;
+        ECHO_OFF.store(true, Ordering::SeqCst);
+    }
+
+    let mut input = String::new();
+    let read = stdin().read_line(&mut input);
+
+    if terminal {
+        restore_echo();
+        // the line break of the answer was not echoed either
+        println!();
+    }
+
+    let answer = match read? {
+        0 => None,
+        _ => Some(
+            input
+                .trim()
+                .to_string(),
+        ),
+    };
+    input.zero();
+    Ok(answer)
+}
+
+/// Turn echo back on if `ask` turned it off.
+pub fn restore_echo() {
+    if ECHO_OFF.swap(false, Ordering::SeqCst) {
+        let _ = set_echo(true);
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn set_echo(enabled: bool) -> io::Result<()> {
+    use std::process::Command;
+
+    // `stty` changes the terminal of its standard input, which is inherited
+    let status = Command::new("stty")
+        .arg(match enabled {
+            true => "echo",
+            false => "-echo",
+        })
+        .status()?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(io::Error::other(format!(
+            "Could not change terminal echo with `stty` ({status})"
+        ))),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn set_echo(enabled: bool) -> io::Result<()> {
+    use std::ffi::c_void;
+
+    const STD_INPUT_HANDLE: u32 = -10i32 as u32;
+    const ENABLE_ECHO_INPUT: u32 = 0x0004;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetStdHandle(std_handle: u32) -> *mut c_void;
+        fn GetConsoleMode(console: *mut c_void, mode: *mut u32) -> i32;
+        fn SetConsoleMode(console: *mut c_void, mode: u32) -> i32;
+    }
+
+    // SAFETY: the handle is only passed back to the console functions
+    unsafe {
+        let console = GetStdHandle(STD_INPUT_HANDLE);
+        let mut mode = 0;
+        if GetConsoleMode(console, &mut mode) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mode = match enabled {
+            true => mode | ENABLE_ECHO_INPUT,
+            false => mode & !ENABLE_ECHO_INPUT,
+        };
+        if SetConsoleMode(console, mode) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Values that can be overwritten with zeros, so they do not stay around in freed memory.
+pub trait Zero {
+    fn zero(&mut self);
+}
+
+impl Zero for String {
+    fn zero(&mut self) {
+        // SAFETY: zeros are valid UTF-8
+        for byte in unsafe { self.as_mut_vec() } {
+            // volatile, so the writes are not optimized away as unused
+            unsafe { ptr::write_volatile(byte, 0) };
+        }
+        self.clear();
+    }
+}
+
+impl<T: Zero> Zero for Vec<T> {
+    fn zero(&mut self) {
+        for value in self.iter_mut() {
+            value.zero();
+        }
+    }
+}
+
+impl<T: Zero> Zero for IndexMap<String, T> {
+    fn zero(&mut self) {
+        for value in self.values_mut() {
+            value.zero();
+        }
+    }
+}
+
+/// Zeroes the value when it is dropped.
+pub struct Zeroing<T: Zero>(pub T);
+
+impl<T: Zero> Deref for Zeroing<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T { &self.0 }
+}
+
+impl<T: Zero> DerefMut for Zeroing<T> {
+    fn deref_mut(&mut self) -> &mut T { &mut self.0 }
+}
+
+impl<T: Zero> Drop for Zeroing<T> {
+    fn drop(&mut self) {
+        self.0
+            .zero();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero() {
+        let mut values = IndexMap::from([(
+            "password".to_string(),
+            vec!["hunter2".to_string()],
+        )]);
+        values.zero();
+        assert_eq!(values["password"], [""]);
+    }
+}